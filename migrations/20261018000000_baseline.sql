@@ -0,0 +1,37 @@
+-- Tables that existed before the project tracked migrations.
+-- IF NOT EXISTS keeps this a no-op on databases created by hand.
+CREATE TABLE IF NOT EXISTS accounts (
+    id UUID PRIMARY KEY,
+    full_name TEXT NOT NULL,
+    username TEXT NOT NULL UNIQUE,
+    password TEXT NOT NULL,
+    created_at TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ
+);
+
+CREATE TABLE IF NOT EXISTS categories (
+    category_id TEXT PRIMARY KEY,
+    category_name TEXT,
+    created_at TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ
+);
+
+CREATE TABLE IF NOT EXISTS products (
+    product_id TEXT PRIMARY KEY,
+    product_name TEXT,
+    price NUMERIC,
+    stock INTEGER,
+    sku TEXT,
+    category_id TEXT REFERENCES categories (category_id),
+    product_image TEXT,
+    created_at TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ
+);
+
+CREATE TABLE IF NOT EXISTS transactions (
+    transaction_id TEXT PRIMARY KEY,
+    transaction_date TIMESTAMPTZ,
+    total_price NUMERIC,
+    item_count INTEGER,
+    transaction_items JSONB NOT NULL
+);
@@ -0,0 +1,6 @@
+CREATE TYPE account_role AS ENUM ('cashier', 'manager', 'owner');
+
+-- Accounts created before roles existed could already do everything,
+-- so they keep that access. New accounts start as cashiers.
+ALTER TABLE accounts ADD COLUMN role account_role NOT NULL DEFAULT 'owner';
+ALTER TABLE accounts ALTER COLUMN role SET DEFAULT 'cashier';
@@ -0,0 +1,19 @@
+CREATE TABLE sessions (
+    session_id UUID PRIMARY KEY,
+    account_id UUID NOT NULL REFERENCES accounts (id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL,
+    revoked_at TIMESTAMPTZ
+);
+
+CREATE INDEX sessions_account_id_idx ON sessions (account_id);
+
+-- Refresh tokens are single-use. Each refresh marks the presented token as
+-- used and issues a new one for the same session; presenting a used token
+-- again revokes the whole session.
+CREATE TABLE refresh_tokens (
+    token_hash TEXT PRIMARY KEY,
+    session_id UUID NOT NULL REFERENCES sessions (session_id) ON DELETE CASCADE,
+    issued_at TIMESTAMPTZ NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ
+);
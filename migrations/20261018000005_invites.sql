@@ -0,0 +1,9 @@
+CREATE TABLE invites (
+    code_hash TEXT PRIMARY KEY,
+    role account_role NOT NULL,
+    created_by UUID REFERENCES accounts (id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ,
+    used_by UUID REFERENCES accounts (id) ON DELETE SET NULL
+);
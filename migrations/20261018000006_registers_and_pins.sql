@@ -0,0 +1,15 @@
+ALTER TABLE accounts ADD COLUMN pin_hash TEXT;
+
+CREATE TABLE registers (
+    register_id TEXT PRIMARY KEY,
+    register_name TEXT NOT NULL,
+    credential_hash TEXT NOT NULL,
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_by UUID REFERENCES accounts (id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ
+);
+
+ALTER TABLE sessions ADD COLUMN register_id TEXT REFERENCES registers (register_id) ON DELETE SET NULL;
+
+CREATE INDEX sessions_register_id_idx ON sessions (register_id) WHERE revoked_at IS NULL;
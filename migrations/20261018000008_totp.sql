@@ -0,0 +1,24 @@
+ALTER TABLE accounts
+    ADD COLUMN totp_secret TEXT,
+    ADD COLUMN totp_pending_secret TEXT,
+    ADD COLUMN totp_enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    -- Last accepted 30-second time step, so a code cannot be replayed.
+    ADD COLUMN totp_last_step BIGINT;
+
+CREATE TABLE recovery_codes (
+    code_hash TEXT PRIMARY KEY,
+    account_id UUID NOT NULL REFERENCES accounts (id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ
+);
+
+CREATE INDEX recovery_codes_account_id_idx ON recovery_codes (account_id);
+
+CREATE TABLE role_policies (
+    role account_role PRIMARY KEY,
+    require_totp BOOLEAN NOT NULL DEFAULT FALSE,
+    updated_by UUID REFERENCES accounts (id) ON DELETE SET NULL,
+    updated_at TIMESTAMPTZ
+);
+
+INSERT INTO role_policies (role) VALUES ('cashier'), ('manager'), ('owner');
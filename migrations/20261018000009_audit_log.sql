@@ -0,0 +1,20 @@
+CREATE TYPE audit_outcome AS ENUM ('success', 'failure');
+
+CREATE TABLE audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    event_type TEXT NOT NULL,
+    outcome audit_outcome NOT NULL,
+    -- The account that acted, or that a failed login was aimed at.
+    actor_id UUID REFERENCES accounts (id) ON DELETE SET NULL,
+    -- Username as given, kept even when it matches no account.
+    username TEXT,
+    -- What was acted upon, e.g. an account ID or API key ID.
+    target TEXT,
+    ip_address TEXT,
+    user_agent TEXT,
+    detail TEXT,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX audit_log_created_at_idx ON audit_log (created_at);
+CREATE INDEX audit_log_actor_id_idx ON audit_log (actor_id, created_at);
@@ -0,0 +1,59 @@
+CREATE TABLE stores (
+    store_id TEXT PRIMARY KEY,
+    store_name TEXT NOT NULL,
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ
+);
+
+-- Which stores an account works in. Owners can act in every store without
+-- a membership row.
+CREATE TABLE account_stores (
+    account_id UUID NOT NULL REFERENCES accounts (id) ON DELETE CASCADE,
+    store_id TEXT NOT NULL REFERENCES stores (store_id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (account_id, store_id)
+);
+
+CREATE INDEX account_stores_store_id_idx ON account_stores (store_id);
+
+-- Everything that existed before stores belongs to a single default store.
+INSERT INTO stores (store_id, store_name, created_at, updated_at)
+VALUES ('main', 'Main store', NOW(), NOW());
+
+INSERT INTO account_stores (account_id, store_id, created_at)
+SELECT id, 'main', NOW() FROM accounts;
+
+ALTER TABLE categories ADD COLUMN store_id TEXT REFERENCES stores (store_id);
+ALTER TABLE products ADD COLUMN store_id TEXT REFERENCES stores (store_id);
+ALTER TABLE transactions ADD COLUMN store_id TEXT REFERENCES stores (store_id);
+ALTER TABLE registers ADD COLUMN store_id TEXT REFERENCES stores (store_id);
+ALTER TABLE api_keys ADD COLUMN store_id TEXT REFERENCES stores (store_id);
+ALTER TABLE invites ADD COLUMN store_id TEXT REFERENCES stores (store_id);
+ALTER TABLE sessions ADD COLUMN store_id TEXT REFERENCES stores (store_id);
+
+UPDATE categories SET store_id = 'main';
+UPDATE products SET store_id = 'main';
+UPDATE transactions SET store_id = 'main';
+UPDATE registers SET store_id = 'main';
+UPDATE api_keys SET store_id = 'main';
+UPDATE invites SET store_id = 'main';
+UPDATE sessions SET store_id = 'main';
+
+ALTER TABLE categories ALTER COLUMN store_id SET NOT NULL;
+ALTER TABLE products ALTER COLUMN store_id SET NOT NULL;
+ALTER TABLE transactions ALTER COLUMN store_id SET NOT NULL;
+ALTER TABLE registers ALTER COLUMN store_id SET NOT NULL;
+ALTER TABLE api_keys ALTER COLUMN store_id SET NOT NULL;
+ALTER TABLE invites ALTER COLUMN store_id SET NOT NULL;
+ALTER TABLE sessions ALTER COLUMN store_id SET NOT NULL;
+
+-- A product may only be filed under a category of its own store.
+ALTER TABLE categories ADD CONSTRAINT categories_store_id_category_id_key UNIQUE (store_id, category_id);
+ALTER TABLE products
+    ADD CONSTRAINT products_store_category_fkey
+    FOREIGN KEY (store_id, category_id) REFERENCES categories (store_id, category_id);
+
+CREATE INDEX categories_store_id_idx ON categories (store_id);
+CREATE INDEX products_store_id_idx ON products (store_id);
+CREATE INDEX transactions_store_id_idx ON transactions (store_id, transaction_date);
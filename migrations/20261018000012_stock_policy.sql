@@ -0,0 +1,4 @@
+CREATE TYPE stock_policy AS ENUM ('reject', 'allow_negative', 'warn');
+
+-- Products without a stock level are not tracked and never block a sale.
+ALTER TABLE stores ADD COLUMN stock_policy stock_policy NOT NULL DEFAULT 'reject';
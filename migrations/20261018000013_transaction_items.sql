@@ -0,0 +1,34 @@
+CREATE TABLE transaction_items (
+    transaction_id TEXT NOT NULL REFERENCES transactions (transaction_id) ON DELETE CASCADE,
+    line_number INTEGER NOT NULL,
+    -- Lines keep their snapshot when the product is deleted later.
+    product_id TEXT REFERENCES products (product_id) ON DELETE SET NULL,
+    product_name TEXT,
+    product_category TEXT,
+    sku TEXT,
+    price NUMERIC NOT NULL,
+    quantity INTEGER NOT NULL,
+    line_discount NUMERIC NOT NULL DEFAULT 0,
+    line_tax NUMERIC NOT NULL DEFAULT 0,
+    PRIMARY KEY (transaction_id, line_number)
+);
+
+CREATE INDEX transaction_items_product_id_idx ON transaction_items (product_id);
+
+-- Older lines only carry a name, category, quantity and price; lines priced
+-- by the server also reference the product.
+INSERT INTO transaction_items (transaction_id, line_number, product_id, product_name, product_category, sku, price, quantity)
+SELECT
+    transactions.transaction_id,
+    item.line_number,
+    products.product_id,
+    item.line ->> 'product_name',
+    item.line ->> 'product_category',
+    products.sku,
+    COALESCE((item.line ->> 'price')::NUMERIC, 0),
+    COALESCE((item.line ->> 'quantity')::INTEGER, 0)
+FROM transactions
+CROSS JOIN LATERAL jsonb_array_elements(transactions.transaction_items) WITH ORDINALITY AS item (line, line_number)
+LEFT JOIN products ON products.product_id = item.line ->> 'product_id';
+
+ALTER TABLE transactions DROP COLUMN transaction_items;
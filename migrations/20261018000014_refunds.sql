@@ -0,0 +1,14 @@
+CREATE TYPE transaction_type AS ENUM ('sale', 'refund');
+
+ALTER TABLE transactions
+    ADD COLUMN transaction_type transaction_type NOT NULL DEFAULT 'sale',
+    ADD COLUMN original_transaction_id TEXT REFERENCES transactions (transaction_id),
+    ADD COLUMN session_id UUID REFERENCES sessions (session_id) ON DELETE SET NULL,
+    ADD COLUMN reason TEXT,
+    ADD COLUMN voided_at TIMESTAMPTZ,
+    ADD COLUMN voided_by UUID REFERENCES accounts (id);
+
+CREATE INDEX transactions_original_transaction_id_idx ON transactions (original_transaction_id);
+
+-- Refund lines point back at the sale line they return.
+ALTER TABLE transaction_items ADD COLUMN original_line_number INTEGER;
@@ -0,0 +1,12 @@
+CREATE TYPE tender_type AS ENUM ('cash', 'card', 'mobile', 'voucher', 'other');
+
+CREATE TABLE payments (
+    transaction_id TEXT NOT NULL REFERENCES transactions (transaction_id) ON DELETE CASCADE,
+    payment_number INTEGER NOT NULL,
+    tender_type tender_type NOT NULL,
+    amount NUMERIC NOT NULL,
+    reference TEXT,
+    PRIMARY KEY (transaction_id, payment_number)
+);
+
+ALTER TABLE transactions ADD COLUMN change_due NUMERIC NOT NULL DEFAULT 0;
@@ -0,0 +1,51 @@
+CREATE TABLE tax_classes (
+    tax_class_id TEXT PRIMARY KEY,
+    store_id TEXT NOT NULL REFERENCES stores (store_id),
+    class_name TEXT NOT NULL,
+    created_at TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ,
+    UNIQUE (store_id, class_name),
+    UNIQUE (store_id, tax_class_id)
+);
+
+-- Rates are percentages. A class with several rates (e.g. state and city)
+-- charges each of them on the same taxable amount; a class without rates is
+-- exempt.
+CREATE TABLE tax_rates (
+    tax_rate_id TEXT PRIMARY KEY,
+    tax_class_id TEXT NOT NULL REFERENCES tax_classes (tax_class_id) ON DELETE CASCADE,
+    rate_name TEXT NOT NULL,
+    rate NUMERIC NOT NULL CHECK (rate >= 0 AND rate <= 100),
+    created_at TIMESTAMPTZ
+);
+
+CREATE INDEX tax_rates_tax_class_id_idx ON tax_rates (tax_class_id);
+
+ALTER TABLE products ADD COLUMN tax_class_id TEXT;
+ALTER TABLE products
+    ADD FOREIGN KEY (store_id, tax_class_id) REFERENCES tax_classes (store_id, tax_class_id);
+
+ALTER TABLE stores ADD COLUMN prices_include_tax BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE transactions
+    ADD COLUMN tax_total NUMERIC NOT NULL DEFAULT 0,
+    ADD COLUMN prices_include_tax BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- What the customer pays for the line, tax included.
+ALTER TABLE transaction_items ADD COLUMN line_total NUMERIC;
+UPDATE transaction_items SET line_total = price * quantity - line_discount + line_tax;
+ALTER TABLE transaction_items ALTER COLUMN line_total SET NOT NULL;
+
+CREATE TABLE transaction_item_taxes (
+    transaction_id TEXT NOT NULL,
+    line_number INTEGER NOT NULL,
+    tax_number INTEGER NOT NULL,
+    tax_rate_id TEXT REFERENCES tax_rates (tax_rate_id) ON DELETE SET NULL,
+    rate_name TEXT NOT NULL,
+    rate NUMERIC NOT NULL,
+    taxable_amount NUMERIC NOT NULL,
+    tax_amount NUMERIC NOT NULL,
+    PRIMARY KEY (transaction_id, line_number, tax_number),
+    FOREIGN KEY (transaction_id, line_number)
+        REFERENCES transaction_items (transaction_id, line_number) ON DELETE CASCADE
+);
@@ -0,0 +1,56 @@
+CREATE TYPE promotion_type AS ENUM ('buy_x_get_y', 'quantity_break', 'category_percent');
+
+-- A promotion takes `discount_percent` off the matching units: the free units
+-- of every buy X get Y group, the whole quantity once it reaches
+-- `min_quantity`, or every product of a category. It only applies between
+-- `starts_at` and `ends_at`, and, when a daily window is set, between
+-- `daily_start` and `daily_end` (UTC, may wrap past midnight).
+CREATE TABLE promotions (
+    promotion_id TEXT PRIMARY KEY,
+    store_id TEXT NOT NULL REFERENCES stores (store_id),
+    promotion_name TEXT NOT NULL,
+    promotion_type promotion_type NOT NULL,
+    product_id TEXT REFERENCES products (product_id) ON DELETE CASCADE,
+    category_id TEXT REFERENCES categories (category_id) ON DELETE CASCADE,
+    buy_quantity INTEGER CHECK (buy_quantity > 0),
+    get_quantity INTEGER CHECK (get_quantity > 0),
+    min_quantity INTEGER CHECK (min_quantity > 0),
+    discount_percent NUMERIC NOT NULL CHECK (discount_percent > 0 AND discount_percent <= 100),
+    starts_at TIMESTAMPTZ,
+    ends_at TIMESTAMPTZ,
+    daily_start TIME,
+    daily_end TIME,
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ,
+    CHECK ((daily_start IS NULL) = (daily_end IS NULL))
+);
+
+CREATE INDEX promotions_store_id_idx ON promotions (store_id);
+
+CREATE TYPE discount_source AS ENUM ('promotion', 'line', 'order');
+
+ALTER TABLE transactions ADD COLUMN discount_total NUMERIC NOT NULL DEFAULT 0;
+UPDATE transactions
+SET discount_total = COALESCE((
+    SELECT SUM(line_discount) FROM transaction_items
+    WHERE transaction_items.transaction_id = transactions.transaction_id
+), 0);
+
+-- Every discount given on a sale. Promotion and line discounts belong to a
+-- line; an order discount has no line and is spread over the lines'
+-- `line_discount`.
+CREATE TABLE transaction_discounts (
+    transaction_id TEXT NOT NULL REFERENCES transactions (transaction_id) ON DELETE CASCADE,
+    discount_number INTEGER NOT NULL,
+    line_number INTEGER,
+    discount_source discount_source NOT NULL,
+    promotion_id TEXT REFERENCES promotions (promotion_id) ON DELETE SET NULL,
+    description TEXT NOT NULL,
+    amount NUMERIC NOT NULL,
+    PRIMARY KEY (transaction_id, discount_number),
+    FOREIGN KEY (transaction_id, line_number)
+        REFERENCES transaction_items (transaction_id, line_number) ON DELETE CASCADE
+);
+
+CREATE INDEX transaction_discounts_promotion_id_idx ON transaction_discounts (promotion_id);
@@ -0,0 +1,38 @@
+CREATE TYPE discount_type AS ENUM ('percent', 'fixed');
+
+-- Codes are stored upper case and matched case-insensitively. A use is a
+-- redemption on a sale that was not voided; `min_basket` is compared with the
+-- sale's amount after promotions and line discounts.
+CREATE TABLE coupons (
+    coupon_id TEXT PRIMARY KEY,
+    store_id TEXT NOT NULL REFERENCES stores (store_id),
+    code TEXT NOT NULL,
+    discount_type discount_type NOT NULL,
+    discount_value NUMERIC NOT NULL CHECK (discount_value > 0),
+    valid_from TIMESTAMPTZ,
+    valid_until TIMESTAMPTZ,
+    max_uses INTEGER CHECK (max_uses > 0),
+    max_uses_per_customer INTEGER CHECK (max_uses_per_customer > 0),
+    min_basket NUMERIC CHECK (min_basket >= 0),
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ,
+    CHECK (discount_type = 'fixed' OR discount_value <= 100),
+    UNIQUE (store_id, code)
+);
+
+-- One coupon per sale.
+CREATE TABLE coupon_redemptions (
+    transaction_id TEXT PRIMARY KEY REFERENCES transactions (transaction_id) ON DELETE CASCADE,
+    coupon_id TEXT NOT NULL REFERENCES coupons (coupon_id),
+    customer_ref TEXT,
+    amount NUMERIC NOT NULL,
+    redeemed_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX coupon_redemptions_coupon_id_idx ON coupon_redemptions (coupon_id, customer_ref);
+
+ALTER TYPE discount_source ADD VALUE 'coupon';
+
+ALTER TABLE transaction_discounts
+    ADD COLUMN coupon_id TEXT REFERENCES coupons (coupon_id) ON DELETE SET NULL;
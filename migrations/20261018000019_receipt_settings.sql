@@ -0,0 +1,6 @@
+-- Printed on receipts. `address` may span several lines.
+ALTER TABLE stores
+    ADD COLUMN address TEXT,
+    ADD COLUMN tax_id TEXT,
+    ADD COLUMN receipt_header TEXT,
+    ADD COLUMN receipt_footer TEXT;
@@ -0,0 +1,17 @@
+-- Invoice numbers run per store without gaps: the counter is taken in the
+-- same transaction that records the invoice, so a failed attempt gives its
+-- number back.
+ALTER TABLE stores ADD COLUMN last_invoice_number BIGINT NOT NULL DEFAULT 0;
+
+CREATE TABLE invoices (
+    store_id TEXT NOT NULL REFERENCES stores (store_id),
+    invoice_number BIGINT NOT NULL,
+    transaction_id TEXT NOT NULL UNIQUE REFERENCES transactions (transaction_id),
+    customer_name TEXT NOT NULL,
+    company_name TEXT,
+    billing_address TEXT NOT NULL,
+    customer_tax_id TEXT,
+    customer_email TEXT,
+    issued_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (store_id, invoice_number)
+);
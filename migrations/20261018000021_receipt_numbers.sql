@@ -0,0 +1,51 @@
+-- Short numbers for stores and registers, printed in receipt numbers such as
+-- S01-R02-000123. Register numbers count per store; 0 stands for sales made
+-- without a register.
+ALTER TABLE stores ADD COLUMN store_number SERIAL UNIQUE;
+ALTER TABLE stores ADD COLUMN last_register_number INT NOT NULL DEFAULT 0;
+
+ALTER TABLE registers ADD COLUMN register_number INT;
+
+UPDATE registers
+SET register_number = numbered.register_number
+FROM (
+    SELECT register_id, ROW_NUMBER() OVER (PARTITION BY store_id ORDER BY created_at, register_id) AS register_number
+    FROM registers
+) AS numbered
+WHERE registers.register_id = numbered.register_id;
+
+ALTER TABLE registers ALTER COLUMN register_number SET NOT NULL;
+ALTER TABLE registers ADD UNIQUE (store_id, register_number);
+
+UPDATE stores
+SET last_register_number = COALESCE((SELECT MAX(register_number) FROM registers WHERE registers.store_id = stores.store_id), 0);
+
+-- The last receipt number handed out per register. The row is locked by the
+-- sale that takes the next number until it commits, so numbers are unique and
+-- a rolled back sale gives its number back.
+CREATE TABLE receipt_sequences (
+    store_id TEXT NOT NULL REFERENCES stores (store_id),
+    register_number INT NOT NULL,
+    last_number BIGINT NOT NULL,
+    PRIMARY KEY (store_id, register_number)
+);
+
+ALTER TABLE transactions ADD COLUMN receipt_number TEXT;
+
+-- Existing transactions are numbered in date order as made without a register.
+UPDATE transactions
+SET receipt_number = 'S' || REPEAT('0', 2 - LENGTH(stores.store_number::TEXT)) || stores.store_number::TEXT || '-R00-'
+    || REPEAT('0', 6 - LENGTH(numbered.number::TEXT)) || numbered.number::TEXT
+FROM (
+    SELECT transaction_id, ROW_NUMBER() OVER (PARTITION BY store_id ORDER BY transaction_date, transaction_id) AS number
+    FROM transactions
+) AS numbered, stores
+WHERE transactions.transaction_id = numbered.transaction_id AND stores.store_id = transactions.store_id;
+
+INSERT INTO receipt_sequences (store_id, register_number, last_number)
+SELECT store_id, 0, COUNT(*)
+FROM transactions
+GROUP BY store_id;
+
+ALTER TABLE transactions ALTER COLUMN receipt_number SET NOT NULL;
+ALTER TABLE transactions ADD UNIQUE (receipt_number);
@@ -0,0 +1,12 @@
+-- Who rang up each transaction, and at which register. Sales made through an
+-- API key are attributed to the account that created the key.
+ALTER TABLE transactions
+    ADD COLUMN cashier_id UUID REFERENCES accounts (id) ON DELETE SET NULL,
+    ADD COLUMN register_id TEXT REFERENCES registers (register_id) ON DELETE SET NULL;
+
+UPDATE transactions
+SET cashier_id = sessions.account_id, register_id = sessions.register_id
+FROM sessions
+WHERE sessions.session_id = transactions.session_id;
+
+CREATE INDEX transactions_cashier_id_idx ON transactions (store_id, cashier_id);
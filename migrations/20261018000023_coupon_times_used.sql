@@ -0,0 +1,15 @@
+-- Uses are counted on the coupon itself, so a sale can claim one with a
+-- single conditional update instead of counting redemptions. Voiding a sale
+-- gives its use back.
+ALTER TABLE coupons ADD COLUMN times_used INTEGER NOT NULL DEFAULT 0 CHECK (times_used >= 0);
+
+UPDATE coupons
+SET times_used = uses.count
+FROM (
+    SELECT coupon_redemptions.coupon_id, COUNT(*) AS count
+    FROM coupon_redemptions
+    JOIN transactions ON transactions.transaction_id = coupon_redemptions.transaction_id
+    WHERE transactions.voided_at IS NULL
+    GROUP BY coupon_redemptions.coupon_id
+) AS uses
+WHERE uses.coupon_id = coupons.coupon_id;
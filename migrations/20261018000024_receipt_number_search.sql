@@ -0,0 +1,5 @@
+-- Receipt numbers are searched by any part, e.g. the last digits read off a
+-- crumpled receipt, which only a trigram index can serve.
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX transactions_receipt_number_trgm_idx ON transactions USING GIN (receipt_number gin_trgm_ops);
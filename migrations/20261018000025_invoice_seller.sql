@@ -0,0 +1,14 @@
+-- An invoice keeps the seller's legal details as they were when it was
+-- issued, so editing the store later does not change invoices already given
+-- out. Existing invoices take the store's current details.
+ALTER TABLE invoices
+    ADD COLUMN seller_name TEXT,
+    ADD COLUMN seller_address TEXT,
+    ADD COLUMN seller_tax_id TEXT;
+
+UPDATE invoices
+SET seller_name = stores.store_name, seller_address = stores.address, seller_tax_id = stores.tax_id
+FROM stores
+WHERE stores.store_id = invoices.store_id;
+
+ALTER TABLE invoices ALTER COLUMN seller_name SET NOT NULL;
@@ -2,11 +2,13 @@ use std::time::Duration;
 use s3::{creds::Credentials, Bucket, Region};
 use sqlx::{postgres::PgPoolOptions, PgPool};
 
+use crate::services::jwt_keys::JwtKeys;
+
 #[derive(Debug)]
 pub struct Config {
     pub pool: PgPool,
     pub address: String,
-    pub jwt: String,
+    pub jwt: JwtKeys,
     pub s3: Box<Bucket>,
 }
 
@@ -16,7 +18,9 @@ pub async fn init_config() -> Config {
     let server_address = std::env::var("SERVER_ADDRESS").expect("SERVER_ADDRESS not found in env file");
     let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL not found in env file");
     
-    let jwt_secret = std::env::var("JWT_SECRET").expect("JWT_SECRET not found in env file");
+    let jwt_signing_key_id = std::env::var("JWT_SIGNING_KEY_ID").expect("JWT_SIGNING_KEY_ID not found in env file");
+    let jwt_signing_key = std::env::var("JWT_SIGNING_KEY").expect("JWT_SIGNING_KEY not found in env file");
+    let jwt_public_keys = std::env::var("JWT_PUBLIC_KEYS").expect("JWT_PUBLIC_KEYS not found in env file");
 
     let s3_endpoint = std::env::var("S3_ENDPOINT").expect("S3_ENDPOINT not found in env file");
     let s3_access_key = std::env::var("S3_ACCESS_KEY").expect("S3_ACCESS_KEY not found in env file");
@@ -43,12 +47,28 @@ pub async fn init_config() -> Config {
         expiration: None,
     };
 
+    let read_key = |path: &str| {
+        std::fs::read(path).unwrap_or_else(|e| panic!("Can't read JWT key {}: {}", path, e))
+    };
+    let public_keys: Vec<(String, Vec<u8>)> = jwt_public_keys
+        .split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(|entry| {
+            let (kid, path) = entry
+                .split_once('=')
+                .expect("JWT_PUBLIC_KEYS entries must look like <kid>=<path>");
+            (kid.trim().to_string(), read_key(path.trim()))
+        })
+        .collect();
+    let jwt_keys = JwtKeys::from_pem(&jwt_signing_key_id, &read_key(&jwt_signing_key), &public_keys)
+        .unwrap_or_else(|e| panic!("Invalid JWT key configuration: {}", e));
+
     let bucket = Bucket::new(&s3_bucket, region, credentials).unwrap();
 
     Config {
         pool: db_pool,
         address: server_address,
-        jwt: jwt_secret,
+        jwt: jwt_keys,
         s3: bucket,
     }
 }
@@ -0,0 +1,614 @@
+use std::sync::Arc;
+use axum::{extract::{Path, Query, State}, http::StatusCode, response::IntoResponse, Extension, Json};
+use chrono::{Duration, Utc};
+use serde_json::{json, Value};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        accounts_model::{AccountModel, UpdateAccountModel},
+        audit_model::AuditOutcome,
+        auth_model::{CreateInviteModel, Role, SignupModel},
+        filter_model::FilterOptionsModel,
+        stores_model::ActiveStore},
+    services::{audit_service::{self, ClientInfo}, login_throttle::ThrottleKey, token_service},
+    AppState
+};
+
+const PASSWORD_RESET_MINUTES: i64 = 60;
+const INVITE_DAYS: i64 = 7;
+
+/// Rejects demoting or deactivating `account_id` when it is the last active
+/// owner. The owner rows stay locked until the surrounding transaction ends,
+/// so two requests cannot each remove one of the last two owners.
+async fn ensure_other_owner(conn: &mut PgConnection, account_id: Uuid) -> Result<(), (StatusCode, Json<Value>)> {
+    let owners = sqlx::query_scalar!(
+        r#"
+            SELECT id
+            FROM accounts
+            WHERE role = 'owner' AND is_active
+            FOR UPDATE
+        "#,
+    )
+    .fetch_all(conn)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?;
+
+    if owners.iter().all(|owner| *owner == account_id) {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(json!({
+                "success": false,
+                "message": "The last owner cannot be demoted or deactivated",
+            })),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Looks up the target account and rejects the request when it does not exist
+/// in the active store or, unless the acting user is an owner, does not hold a
+/// lower role than theirs. Managers can therefore not reset the password of,
+/// or otherwise take over, another manager.
+async fn ensure_can_manage(
+    app_state: &AppState,
+    user: &SignupModel,
+    store: &ActiveStore,
+    account_id: Uuid,
+) -> Result<Role, (StatusCode, Json<Value>)> {
+    let target_role = sqlx::query_scalar!(
+        r#"
+            SELECT role AS "role: Role"
+            FROM accounts
+            WHERE id = $1
+                AND (role = 'owner' OR EXISTS (
+                    SELECT 1 FROM account_stores WHERE account_id = accounts.id AND store_id = $2
+                ))
+        "#,
+        account_id,
+        store.0,
+    )
+    .fetch_optional(&app_state.db)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?
+    .ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "success": false,
+                "message": "Account not found",
+            })),
+        )
+    })?;
+
+    if user.role != Role::Owner && target_role >= user.role {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "success": false,
+                "message": "You can only manage accounts with a lower role than your own",
+            })),
+        ));
+    }
+
+    Ok(target_role)
+}
+
+/// Lists the accounts working in the active store, including owners.
+pub async fn get_all_accounts(
+    State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
+    Query(filter_options): Query<FilterOptionsModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let limit = filter_options.limit.unwrap_or(10);
+    let offset = (filter_options.offset.unwrap_or(1) - 1) * limit;
+
+    let total_accounts: Option<i64> = sqlx::query_scalar!(
+        r#"
+            SELECT COUNT(*)
+            FROM accounts
+            WHERE role = 'owner' OR EXISTS (
+                SELECT 1 FROM account_stores WHERE account_id = accounts.id AND store_id = $1
+            )
+        "#,
+        store.0,
+    )
+    .fetch_one(&app_state.db)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success" : false,
+                "message" : e.to_string(),
+            })),
+        )
+    })?;
+
+    let accounts = sqlx::query_as!(
+        AccountModel,
+        r#"
+            SELECT id, full_name, username, role AS "role: Role", is_active, created_at, updated_at
+            FROM accounts
+            WHERE role = 'owner' OR EXISTS (
+                SELECT 1 FROM account_stores WHERE account_id = accounts.id AND store_id = $1
+            )
+            ORDER BY username
+            OFFSET $2
+            LIMIT $3
+        "#,
+        store.0,
+        offset,
+        limit,
+    )
+    .fetch_all(&app_state.db)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?;
+
+    let json_response = json!({
+        "success": true,
+        "data": accounts,
+        "total": total_accounts,
+        "offset": offset,
+        "limit": limit,
+    });
+
+    Ok((
+        StatusCode::OK,
+        Json(json_response),
+    ))
+}
+
+pub async fn get_account(
+    State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
+    Path(account_id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let account = fetch_account(&app_state, &store, account_id).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+            "data": account,
+        })),
+    ))
+}
+
+pub async fn update_account(
+    State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(user): Extension<SignupModel>,
+    Extension(store): Extension<ActiveStore>,
+    Path(account_id): Path<Uuid>,
+    Json(update_account): Json<UpdateAccountModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let target_role = ensure_can_manage(&app_state, &user, &store, account_id).await?;
+
+    if let Some(role) = update_account.role {
+        if user.id == Some(account_id) {
+            return Err((
+                StatusCode::FORBIDDEN,
+                Json(json!({
+                    "success": false,
+                    "message": "You cannot change your own role",
+                })),
+            ));
+        }
+        if user.role != Role::Owner && role >= user.role {
+            return Err((
+                StatusCode::FORBIDDEN,
+                Json(json!({
+                    "success": false,
+                    "message": "You can only assign roles lower than your own",
+                })),
+            ));
+        }
+    }
+
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    };
+
+    let mut tx = app_state.db.begin().await.map_err(db_error)?;
+
+    // Owners need no store membership, so a demoted owner joins the store it
+    // was demoted in to keep working there.
+    if target_role == Role::Owner && update_account.role.is_some_and(|role| role != Role::Owner) {
+        ensure_other_owner(&mut tx, account_id).await?;
+
+        sqlx::query!(
+            r#"
+                INSERT INTO account_stores (account_id, store_id, created_at)
+                VALUES ($1, $2, $3)
+                ON CONFLICT DO NOTHING
+            "#,
+            account_id,
+            store.0,
+            Utc::now(),
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+    }
+
+    sqlx::query!(
+        r#"
+            UPDATE accounts
+            SET
+                full_name = COALESCE($1, full_name),
+                role = COALESCE($2, role),
+                updated_at = $3
+            WHERE id = $4
+        "#,
+        update_account.full_name,
+        update_account.role as Option<Role>,
+        Utc::now(),
+        account_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let account = fetch_account(&app_state, &store, account_id).await?;
+
+    let event = client.event("account_update").actor(user.id).target(account_id);
+    audit_service::record(&app_state.db, event, AuditOutcome::Success).await;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+            "data": account,
+        })),
+    ))
+}
+
+pub async fn deactivate_account(
+    State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(user): Extension<SignupModel>,
+    Extension(store): Extension<ActiveStore>,
+    Path(account_id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    if user.id == Some(account_id) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "success": false,
+                "message": "You cannot deactivate your own account",
+            })),
+        ));
+    }
+
+    set_account_active(&app_state, &client, &user, &store, account_id, false).await
+}
+
+pub async fn reactivate_account(
+    State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(user): Extension<SignupModel>,
+    Extension(store): Extension<ActiveStore>,
+    Path(account_id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    set_account_active(&app_state, &client, &user, &store, account_id, true).await
+}
+
+async fn set_account_active(
+    app_state: &AppState,
+    client: &ClientInfo,
+    user: &SignupModel,
+    store: &ActiveStore,
+    account_id: Uuid,
+    is_active: bool,
+) -> Result<(StatusCode, Json<Value>), (StatusCode, Json<Value>)> {
+
+    let target_role = ensure_can_manage(app_state, user, store, account_id).await?;
+
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    };
+
+    let mut tx = app_state.db.begin().await.map_err(db_error)?;
+
+    if !is_active && target_role == Role::Owner {
+        ensure_other_owner(&mut tx, account_id).await?;
+    }
+
+    sqlx::query!(
+        "UPDATE accounts SET is_active = $1, updated_at = $2 WHERE id = $3",
+        is_active,
+        Utc::now(),
+        account_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    if !is_active {
+        token_service::revoke_account_sessions(&mut *tx, account_id).await?;
+    }
+
+    tx.commit().await.map_err(db_error)?;
+
+    let event_type = if is_active { "account_reactivate" } else { "account_deactivate" };
+    let event = client.event(event_type).actor(user.id).target(account_id);
+    audit_service::record(&app_state.db, event, AuditOutcome::Success).await;
+
+    let account = fetch_account(app_state, store, account_id).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+            "data": account,
+        })),
+    ))
+}
+
+async fn fetch_account(
+    app_state: &AppState,
+    store: &ActiveStore,
+    account_id: Uuid,
+) -> Result<AccountModel, (StatusCode, Json<Value>)> {
+    sqlx::query_as!(
+        AccountModel,
+        r#"
+            SELECT id, full_name, username, role AS "role: Role", is_active, created_at, updated_at
+            FROM accounts
+            WHERE id = $1
+                AND (role = 'owner' OR EXISTS (
+                    SELECT 1 FROM account_stores WHERE account_id = accounts.id AND store_id = $2
+                ))
+        "#,
+        account_id,
+        store.0,
+    )
+    .fetch_optional(&app_state.db)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?
+    .ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "success": false,
+                "message": "Account not found",
+            })),
+        )
+    })
+}
+
+pub async fn unlock_account(
+    State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(user): Extension<SignupModel>,
+    Extension(store): Extension<ActiveStore>,
+    Path(account_id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    ensure_can_manage(&app_state, &user, &store, account_id).await?;
+    let account = fetch_account(&app_state, &store, account_id).await?;
+
+    app_state.login_throttle.clear(&ThrottleKey::Username(account.username));
+
+    let event = client.event("account_unlock").actor(user.id).target(account_id);
+    audit_service::record(&app_state.db, event, AuditOutcome::Success).await;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+        })),
+    ))
+}
+
+pub async fn revoke_account_sessions(
+    State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(user): Extension<SignupModel>,
+    Extension(store): Extension<ActiveStore>,
+    Path(account_id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    ensure_can_manage(&app_state, &user, &store, account_id).await?;
+
+    let revoked = token_service::revoke_account_sessions(&app_state.db, account_id).await?;
+
+    let event = client
+        .event("account_sessions_revoke")
+        .actor(user.id)
+        .target(account_id)
+        .detail(format!("{} sessions revoked", revoked));
+    audit_service::record(&app_state.db, event, AuditOutcome::Success).await;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+            "revoked_sessions": revoked,
+        })),
+    ))
+}
+
+pub async fn create_password_reset(
+    State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(user): Extension<SignupModel>,
+    Extension(store): Extension<ActiveStore>,
+    Path(account_id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    ensure_can_manage(&app_state, &user, &store, account_id).await?;
+
+    let reset_token = token_service::generate_secret();
+    let now = Utc::now();
+    let expires_at = now + Duration::minutes(PASSWORD_RESET_MINUTES);
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    };
+
+    let mut tx = app_state.db.begin().await.map_err(db_error)?;
+
+    // Only the most recently issued reset token stays usable.
+    sqlx::query!(
+        r#"
+            UPDATE password_resets
+            SET used_at = $1
+            WHERE account_id = $2 AND used_at IS NULL
+        "#,
+        now,
+        account_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    sqlx::query!(
+        r#"
+            INSERT INTO password_resets (token_hash, account_id, created_by, created_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5)
+        "#,
+        token_service::hash_secret(&reset_token),
+        account_id,
+        user.id,
+        now,
+        expires_at,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let event = client.event("password_reset_issue").actor(user.id).target(account_id);
+    audit_service::record(&app_state.db, event, AuditOutcome::Success).await;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({
+            "success": true,
+            "reset_token": reset_token,
+            "expires_at": expires_at,
+        })),
+    ))
+}
+
+pub async fn create_invite(
+    State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(user): Extension<SignupModel>,
+    Extension(store): Extension<ActiveStore>,
+    Json(invite): Json<CreateInviteModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let role = invite.role.unwrap_or_default();
+    if user.role != Role::Owner && role >= user.role {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "success": false,
+                "message": "You can only assign roles lower than your own",
+            })),
+        ));
+    }
+
+    let invite_code = token_service::generate_secret();
+    let now = Utc::now();
+    let expires_at = now + Duration::days(INVITE_DAYS);
+
+    sqlx::query!(
+        r#"
+            INSERT INTO invites (code_hash, role, created_by, created_at, expires_at, store_id)
+            VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        token_service::hash_secret(&invite_code),
+        role as Role,
+        user.id,
+        now,
+        expires_at,
+        store.0,
+    )
+    .execute(&app_state.db)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?;
+
+    let event = client.event("invite_create").actor(user.id).detail(format!("role: {}", role.as_str()));
+    audit_service::record(&app_state.db, event, AuditOutcome::Success).await;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({
+            "success": true,
+            "invite_code": invite_code,
+            "role": role,
+            "expires_at": expires_at,
+        })),
+    ))
+}
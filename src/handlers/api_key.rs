@@ -0,0 +1,194 @@
+use std::sync::Arc;
+use axum::{extract::{Path, Query, State}, http::StatusCode, response::IntoResponse, Extension, Json};
+use serde_json::{json, Value};
+use uuid::Uuid;
+use chrono::Utc;
+
+use crate::{
+    models::{
+        api_keys_model::{ApiKeyModel, CreateApiKeyModel, API_KEY_SCOPES},
+        audit_model::AuditOutcome,
+        auth_model::SignupModel,
+        filter_model::FilterOptionsModel,
+        stores_model::ActiveStore},
+    services::{
+        audit_service::{self, ClientInfo},
+        token_service::{generate_secret, hash_secret}},
+    AppState
+};
+
+pub async fn get_all_api_keys(
+    State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
+    Query(filter_options): Query<FilterOptionsModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let limit = filter_options.limit.unwrap_or(10);
+    let offset = (filter_options.offset.unwrap_or(1) - 1) * limit;
+
+    let total_api_keys: Option<i64> = sqlx::query_scalar!(
+        r#"
+            SELECT COUNT(*)
+            FROM api_keys
+            WHERE store_id = $1
+        "#,
+        store.0,
+    )
+    .fetch_one(&app_state.db)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success" : false,
+                "message" : e.to_string(),
+            })),
+        )
+    })?;
+
+    let api_keys = sqlx::query_as!(
+        ApiKeyModel,
+        r#"
+            SELECT key_id, key_name, scopes, created_by, created_at, last_used_at, revoked_at
+            FROM api_keys
+            WHERE store_id = $1
+            ORDER BY created_at DESC
+            OFFSET $2
+            LIMIT $3
+        "#,
+        store.0,
+        offset,
+        limit,
+    )
+    .fetch_all(&app_state.db)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?;
+
+    let json_response = json!({
+        "success": true,
+        "data": api_keys,
+        "total": total_api_keys,
+        "offset": offset,
+        "limit": limit,
+    });
+
+    Ok((
+        StatusCode::OK,
+        Json(json_response),
+    ))
+}
+
+/// The full key is only returned here; the server keeps just its hash.
+pub async fn create_api_key(
+    State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(user): Extension<SignupModel>,
+    Extension(store): Extension<ActiveStore>,
+    Json(api_key): Json<CreateApiKeyModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    if let Some(scope) = api_key.scopes.iter().find(|scope| !API_KEY_SCOPES.contains(&scope.as_str())) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "success": false,
+                "message": format!("Unknown scope: {}", scope),
+            })),
+        ));
+    }
+
+    let key_id = data_encoding::BASE64URL_NOPAD.encode( Uuid::new_v4().as_bytes());
+    let key = format!("{}.{}", key_id, generate_secret());
+
+    let api_key = sqlx::query_as!(
+        ApiKeyModel,
+        r#"
+            INSERT INTO api_keys (key_id, key_name, key_hash, scopes, created_by, created_at, store_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING key_id, key_name, scopes, created_by, created_at, last_used_at, revoked_at
+        "#,
+        key_id,
+        api_key.key_name,
+        hash_secret(&key),
+        &api_key.scopes,
+        user.id,
+        Utc::now(),
+        store.0,
+    )
+    .fetch_one(&app_state.db)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?;
+
+    let event = client
+        .event("api_key_create")
+        .actor(user.id)
+        .target(&api_key.key_id)
+        .detail(api_key.scopes.join(" "));
+    audit_service::record(&app_state.db, event, AuditOutcome::Success).await;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({
+            "success": true,
+            "data": api_key,
+            "key": key,
+        })),
+    ))
+}
+
+pub async fn revoke_api_key(
+    State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(user): Extension<SignupModel>,
+    Extension(store): Extension<ActiveStore>,
+    Path(key_id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    sqlx::query!(
+        r#"
+            UPDATE api_keys
+            SET revoked_at = $1
+            WHERE key_id = $2 AND store_id = $3 AND revoked_at IS NULL
+        "#,
+        Utc::now(),
+        key_id,
+        store.0,
+    )
+    .execute(&app_state.db)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?;
+
+    let event = client.event("api_key_revoke").actor(user.id).target(&key_id);
+    audit_service::record(&app_state.db, event, AuditOutcome::Success).await;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+        })),
+    ))
+}
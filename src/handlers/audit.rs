@@ -0,0 +1,99 @@
+use std::sync::Arc;
+use axum::{extract::{Query, State}, http::StatusCode, response::IntoResponse, Json};
+use serde_json::{json, Value};
+
+use crate::{
+    models::audit_model::{AuditFilterModel, AuditLogModel, AuditOutcome},
+    AppState
+};
+
+pub async fn get_audit_log(
+    State(app_state): State<Arc<AppState>>,
+    Query(filter_options): Query<AuditFilterModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let limit = filter_options.limit.unwrap_or(10);
+    let offset = (filter_options.offset.unwrap_or(1) - 1) * limit;
+    let username = filter_options.username.map(|username| username.to_ascii_lowercase());
+
+    let total_events: Option<i64> = sqlx::query_scalar!(
+        r#"
+            SELECT COUNT(*)
+            FROM audit_log
+            WHERE ($1::TIMESTAMPTZ IS NULL OR created_at >= $1)
+                AND ($2::TIMESTAMPTZ IS NULL OR created_at < $2)
+                AND ($3::UUID IS NULL OR actor_id = $3)
+                AND ($4::TEXT IS NULL OR username = $4)
+                AND ($5::TEXT IS NULL OR event_type = $5)
+                AND ($6::audit_outcome IS NULL OR outcome = $6)
+        "#,
+        filter_options.from,
+        filter_options.to,
+        filter_options.actor_id,
+        username,
+        filter_options.event_type,
+        filter_options.outcome as Option<AuditOutcome>,
+    )
+    .fetch_one(&app_state.db)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success" : false,
+                "message" : e.to_string(),
+            })),
+        )
+    })?;
+
+    let events = sqlx::query_as!(
+        AuditLogModel,
+        r#"
+            SELECT
+                id, event_type, outcome AS "outcome: AuditOutcome", actor_id, username, target,
+                ip_address, user_agent, detail, created_at
+            FROM audit_log
+            WHERE ($1::TIMESTAMPTZ IS NULL OR created_at >= $1)
+                AND ($2::TIMESTAMPTZ IS NULL OR created_at < $2)
+                AND ($3::UUID IS NULL OR actor_id = $3)
+                AND ($4::TEXT IS NULL OR username = $4)
+                AND ($5::TEXT IS NULL OR event_type = $5)
+                AND ($6::audit_outcome IS NULL OR outcome = $6)
+            ORDER BY created_at DESC, id DESC
+            OFFSET $7
+            LIMIT $8
+        "#,
+        filter_options.from,
+        filter_options.to,
+        filter_options.actor_id,
+        username,
+        filter_options.event_type,
+        filter_options.outcome as Option<AuditOutcome>,
+        offset,
+        limit,
+    )
+    .fetch_all(&app_state.db)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?;
+
+    let json_response = json!({
+        "success": true,
+        "data": events,
+        "total": total_events,
+        "offset": offset,
+        "limit": limit,
+    });
+
+    Ok((
+        StatusCode::OK,
+        Json(json_response),
+    ))
+}
@@ -1,20 +1,52 @@
 use std::sync::Arc;
-use argon2::{password_hash::{rand_core::OsRng, PasswordHasher, SaltString}, Argon2, PasswordHash, PasswordVerifier};
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
-use jsonwebtoken::{encode, EncodingKey, Header};
+use axum::{extract::State, http::{header, HeaderMap, StatusCode}, response::IntoResponse, Extension, Json};
 use serde_json::{json, Value};
 use uuid::Uuid;
 use chrono::Utc;
 
-use crate::{models::auth_model::{LoginModel, SignupModel, TokenClaims}, AppState};
+use crate::{
+    middlewares::auth_guard::authenticate,
+    models::{
+        audit_model::AuditOutcome,
+        auth_model::{
+        ChangePasswordModel, LoginModel, PinLoginModel, RefreshModel, ResetPasswordModel, Role, SetPinModel,
+        SignupInputModel, SignupModel, TokenClaims, TotpEnrollLoginModel, TotpLoginModel}},
+    services::{
+        audit_service::{self, ClientInfo},
+        password_service::{hash_password, verify_password},
+        token_service::{
+            self, decode_mfa_token, encode_access_token, encode_mfa_token, hash_secret, insert_refresh_token,
+            issue_register_session, issue_session, ACCESS_TOKEN_MINUTES, MFA_TOKEN_MINUTES, REGISTER_TOKEN_MINUTES},
+        store_service,
+        totp_service,
+    },
+    AppState
+};
 
 pub async fn login(
     State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
     Json(credentials): Json<LoginModel>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+    let result = password_login(&app_state, &credentials).await;
+
+    let event = client.event("login").username(&credentials.username);
+    let event = match &result {
+        Ok((StatusCode::ACCEPTED, _)) => event.detail("Second factor required"),
+        _ => event,
+    };
+    audit_service::record_result(&app_state.db, event, &result).await;
+
+    result
+}
+
+async fn password_login(
+    app_state: &AppState,
+    credentials: &LoginModel,
+) -> Result<(StatusCode, Json<Value>), (StatusCode, Json<Value>)> {
     let user = sqlx::query!(
         r#"
-            SELECT id, username, password
+            SELECT id, username, password, role AS "role: Role", is_active, totp_enabled
             FROM accounts
             WHERE username = $1
         "#,
@@ -36,17 +68,10 @@ pub async fn login(
             "success": false,
             "message": "Invalid email or password",
         });
-        (StatusCode::BAD_REQUEST, Json(error_response))
+        (StatusCode::UNAUTHORIZED, Json(error_response))
     })?;
 
-    let is_valid = match PasswordHash::new(&user.password) {
-        Ok(parsed_hash) => Argon2::default()
-            .verify_password(credentials.password.as_bytes(), &parsed_hash)
-            .is_ok(),
-        Err(_) => false,
-    };
-
-    if !is_valid {
+    if !verify_password(&credentials.password, &user.password) {
         let error_response = json!({
             "success": false,
             "message": "Invalid email or password"
@@ -54,42 +79,226 @@ pub async fn login(
         return Err((StatusCode::UNAUTHORIZED, Json(error_response)));
     }
 
-    let now = chrono::Utc::now();
-    let iat = now.timestamp() as usize;
-    let exp = (now + chrono::Duration::minutes(60)).timestamp() as usize;
-    let claims: TokenClaims = TokenClaims {
-        sub: user.id.to_string(),
-        exp,
-        iat,
-    };
-
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(&app_state.env.as_bytes()),
-    ).map_err(|_| {
+    if !user.is_active {
         let error_response = json!({
             "success": false,
-            "message": "Failed to generate token"
+            "message": "This account has been deactivated"
         });
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
-    })?;
+        return Err((StatusCode::FORBIDDEN, Json(error_response)));
+    }
+
+    let store_id = store_service::resolve_session_store(
+        &app_state.db,
+        user.id,
+        user.role,
+        credentials.store_id.as_deref(),
+    )
+    .await?;
+
+    if user.totp_enabled || totp_service::is_required_for(&app_state.db, user.role).await? {
+        // 202 rather than 200: the login is not complete, so the login
+        // throttle must not treat it as a success.
+        let response_body = json!({
+            "success": true,
+            "mfa_required": true,
+            "mfa_enrollment_required": !user.totp_enabled,
+            "mfa_token": encode_mfa_token(app_state, user.id, &store_id)?,
+            "expires_in": MFA_TOKEN_MINUTES * 60,
+        });
+
+        return Ok((StatusCode::ACCEPTED, Json(response_body)));
+    }
+
+    let tokens = issue_session(app_state, user.id, user.role, &store_id, None).await?;
 
     let response_body = json!({
         "success": true,
-        "token": token
+        "token": tokens.token,
+        "refresh_token": tokens.refresh_token,
+        "expires_in": tokens.expires_in,
     });
 
     Ok((StatusCode::OK, Json(response_body)))
 }
 
-pub async fn signup(
+/// Second login step for accounts with two-factor authentication. Accounts
+/// that must enroll first call `login_totp_enroll` and then confirm their
+/// first code here, which also returns their recovery codes.
+pub async fn login_totp(
+    State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Json(body): Json<TotpLoginModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+    let result = second_factor_login(&app_state, &body).await;
+
+    let event = client.event("login_totp").username(&body.username);
+    audit_service::record_result(&app_state.db, event, &result).await;
+
+    result
+}
+
+async fn second_factor_login(
+    app_state: &AppState,
+    body: &TotpLoginModel,
+) -> Result<(StatusCode, Json<Value>), (StatusCode, Json<Value>)> {
+    let (user, store_id) = pending_mfa_account(app_state, &body.username, &body.mfa_token).await?;
+
+    let recovery_codes = if user.totp_enabled {
+        totp_service::verify_second_factor(&app_state.db, user.id, &body.code).await?;
+        None
+    } else {
+        Some(totp_service::confirm_enrollment(&app_state.db, user.id, &body.code).await?)
+    };
+
+    let tokens = issue_session(app_state, user.id, user.role, &store_id, None).await?;
+
+    let mut response_body = json!({
+        "success": true,
+        "token": tokens.token,
+        "refresh_token": tokens.refresh_token,
+        "expires_in": tokens.expires_in,
+    });
+    if let Some(recovery_codes) = recovery_codes {
+        response_body["recovery_codes"] = json!(recovery_codes);
+    }
+
+    Ok((StatusCode::OK, Json(response_body)))
+}
+
+pub async fn login_totp_enroll(
+    State(app_state): State<Arc<AppState>>,
+    Json(body): Json<TotpEnrollLoginModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+    let (user, _) = pending_mfa_account(&app_state, &body.username, &body.mfa_token).await?;
+
+    if user.totp_enabled {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(json!({
+                "success": false,
+                "message": "Two-factor authentication is already enabled",
+            })),
+        ));
+    }
+
+    let (secret, otpauth_uri) = totp_service::begin_enrollment(&app_state.db, user.id, &user.username).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+            "secret": secret,
+            "otpauth_uri": otpauth_uri,
+        })),
+    ))
+}
+
+struct PendingMfaAccount {
+    id: Uuid,
+    username: String,
+    role: Role,
+    totp_enabled: bool,
+}
+
+/// Resolves the account and store behind an MFA token, checking it matches
+/// `username` so the login throttle counts failures against the right account.
+async fn pending_mfa_account(
+    app_state: &AppState,
+    username: &str,
+    mfa_token: &str,
+) -> Result<(PendingMfaAccount, String), (StatusCode, Json<Value>)> {
+    let (account_id, store_id) = decode_mfa_token(app_state, mfa_token)?;
+
+    let account = sqlx::query_as!(
+        PendingMfaAccount,
+        r#"
+            SELECT id, username, role AS "role: Role", totp_enabled
+            FROM accounts
+            WHERE id = $1 AND username = $2 AND is_active
+        "#,
+        account_id,
+        username.to_ascii_lowercase(),
+    )
+    .fetch_optional(&app_state.db)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": format!("Database error: {}", e),
+            })),
+        )
+    })?
+    .ok_or_else(|| {
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(json!({
+                "success": false,
+                "message": "Invalid or expired login token, please log in again",
+            })),
+        )
+    })?;
+
+    Ok((account, store_id))
+}
+
+pub async fn pin_login(
     State(app_state): State<Arc<AppState>>,
-    Json(credentials): Json<SignupModel>,
+    client: ClientInfo,
+    Json(credentials): Json<PinLoginModel>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+    let result = register_login(&app_state, &credentials).await;
+
+    let event = client
+        .event("pin_login")
+        .username(&credentials.username)
+        .target(&credentials.register_id);
+    audit_service::record_result(&app_state.db, event, &result).await;
+
+    result
+}
+
+async fn register_login(
+    app_state: &AppState,
+    credentials: &PinLoginModel,
+) -> Result<(StatusCode, Json<Value>), (StatusCode, Json<Value>)> {
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": format!("Database error: {}", e),
+            })),
+        )
+    };
+
+    let register_key_hash = hash_secret(&credentials.register_key);
+    let register = sqlx::query!(
+        r#"
+            SELECT register_id, store_id
+            FROM registers
+            WHERE register_id = $1 AND credential_hash = $2 AND is_active
+        "#,
+        credentials.register_id,
+        register_key_hash,
+    )
+    .fetch_optional(&app_state.db)
+    .await
+    .map_err(db_error)?
+    .ok_or_else(|| {
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(json!({
+                "success": false,
+                "message": "Invalid register credentials",
+            })),
+        )
+    })?;
+
     let user = sqlx::query!(
         r#"
-            SELECT username
+            SELECT id, role AS "role: Role", pin_hash, is_active
             FROM accounts
             WHERE username = $1
         "#,
@@ -97,6 +306,173 @@ pub async fn signup(
     )
     .fetch_optional(&app_state.db)
     .await
+    .map_err(db_error)?
+    .filter(|user| {
+        user.is_active
+            && user.pin_hash.as_deref().is_some_and(|pin_hash| verify_password(&credentials.pin, pin_hash))
+    })
+    .ok_or_else(|| {
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(json!({
+                "success": false,
+                "message": "Invalid username or PIN",
+            })),
+        )
+    })?;
+
+    // Only staff of the register's store can sign in on it.
+    if !store_service::can_access(&app_state.db, user.id, user.role, &register.store_id).await.map_err(db_error)? {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(json!({
+                "success": false,
+                "message": "Invalid username or PIN",
+            })),
+        ));
+    }
+
+    let token = issue_register_session(app_state, user.id, &register.register_id, &register.store_id).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+            "token": token,
+            "expires_in": REGISTER_TOKEN_MINUTES * 60,
+        })),
+    ))
+}
+
+pub async fn refresh(
+    State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Json(body): Json<RefreshModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": format!("Database error: {}", e),
+            })),
+        )
+    };
+    let invalid_token = || {
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(json!({
+                "success": false,
+                "message": "Invalid or expired refresh token",
+            })),
+        )
+    };
+
+    let mut tx = app_state.db.begin().await.map_err(db_error)?;
+
+    let stored = sqlx::query!(
+        r#"
+            SELECT
+                refresh_tokens.session_id, refresh_tokens.expires_at, refresh_tokens.used_at,
+                sessions.account_id, sessions.store_id, sessions.revoked_at, accounts.role AS "role: Role",
+                accounts.is_active
+            FROM refresh_tokens
+            JOIN sessions ON sessions.session_id = refresh_tokens.session_id
+            JOIN accounts ON accounts.id = sessions.account_id
+            WHERE refresh_tokens.token_hash = $1
+            FOR UPDATE OF refresh_tokens
+        "#,
+        hash_secret(&body.refresh_token),
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(db_error)?
+    .ok_or_else(invalid_token)?;
+
+    if stored.revoked_at.is_some() || stored.expires_at <= Utc::now() || !stored.is_active {
+        return Err(invalid_token());
+    }
+
+    if stored.used_at.is_some() {
+        // A rotated-out token is being replayed, so treat the session as stolen.
+        sqlx::query!(
+            "UPDATE sessions SET revoked_at = $1 WHERE session_id = $2",
+            Utc::now(),
+            stored.session_id,
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+
+        let event = client
+            .event("refresh_token_reuse")
+            .actor(Some(stored.account_id))
+            .target(stored.session_id)
+            .detail("A used refresh token was replayed; the session was revoked");
+        audit_service::record(&app_state.db, event, AuditOutcome::Failure).await;
+
+        return Err(invalid_token());
+    }
+
+    sqlx::query!(
+        "UPDATE refresh_tokens SET used_at = $1 WHERE token_hash = $2",
+        Utc::now(),
+        hash_secret(&body.refresh_token),
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    let refresh_token = insert_refresh_token(&mut tx, stored.session_id).await?;
+    tx.commit().await.map_err(db_error)?;
+
+    let token = encode_access_token(
+        &app_state,
+        stored.account_id,
+        stored.role,
+        stored.session_id,
+        &stored.store_id,
+    )?;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+            "token": token,
+            "refresh_token": refresh_token,
+            "expires_in": ACCESS_TOKEN_MINUTES * 60,
+        })),
+    ))
+}
+
+pub async fn logout(
+    State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(user): Extension<SignupModel>,
+    Extension(claims): Extension<TokenClaims>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+    let session_id = Uuid::parse_str(&claims.sid).map_err(|_| {
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(json!({
+                "success": false,
+                "message": "Invalid token",
+            })),
+        )
+    })?;
+
+    sqlx::query!(
+        r#"
+            UPDATE sessions
+            SET revoked_at = $1
+            WHERE session_id = $2 AND revoked_at IS NULL
+        "#,
+        Utc::now(),
+        session_id,
+    )
+    .execute(&app_state.db)
+    .await
     .map_err(|e| {
         (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -107,43 +483,262 @@ pub async fn signup(
         )
     })?;
 
-    if user.is_some() {
-        let error_response = json!({
-            "success": false,
-            "message": "Username already taken",
-        });
-        return Err((StatusCode::BAD_REQUEST, Json(error_response)));
+    let event = client.event("logout").actor(user.id).target(session_id);
+    audit_service::record(&app_state.db, event, AuditOutcome::Success).await;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+        })),
+    ))
+}
+
+pub async fn change_password(
+    State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(user): Extension<SignupModel>,
+    Extension(claims): Extension<TokenClaims>,
+    Json(body): Json<ChangePasswordModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+    let result = update_own_password(&app_state, &user, &claims, &body).await;
+
+    let event = client.event("password_change").actor(user.id);
+    audit_service::record_result(&app_state.db, event, &result).await;
+
+    result
+}
+
+async fn update_own_password(
+    app_state: &AppState,
+    user: &SignupModel,
+    claims: &TokenClaims,
+    body: &ChangePasswordModel,
+) -> Result<(StatusCode, Json<Value>), (StatusCode, Json<Value>)> {
+    if !verify_password(&body.current_password, &user.password) {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(json!({
+                "success": false,
+                "message": "Current password is incorrect",
+            })),
+        ));
     }
 
-    let salt = SaltString::generate(&mut OsRng);
-    let hashed_password = Argon2::default()
-        .hash_password(credentials.password.as_bytes(), &salt)
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                    "success": false,
-                    "message": format!("Password hashing error: {}", e),
-                })),
-            )
-        })?;
-
-    sqlx::query_as!(
-        SignupModel,
+    let hashed_password = hash_password(&body.new_password)?;
+    let session_id = Uuid::parse_str(&claims.sid).ok();
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": format!("Database error: {}", e),
+            })),
+        )
+    };
+
+    let mut tx = app_state.db.begin().await.map_err(db_error)?;
+
+    sqlx::query!(
+        "UPDATE accounts SET password = $1, updated_at = $2 WHERE id = $3",
+        hashed_password,
+        Utc::now(),
+        user.id,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    // Keep the session that made the change, end every other one.
+    sqlx::query!(
         r#"
-            INSERT INTO accounts (id, full_name, username, password, created_at, updated_at)
-            VALUES ($1, $2, $3, $4, $5, $6)
+            UPDATE sessions
+            SET revoked_at = $1
+            WHERE account_id = $2 AND session_id IS DISTINCT FROM $3 AND revoked_at IS NULL
         "#,
-        Uuid::new_v4(),
-        credentials.full_name,
-        credentials.username.to_ascii_lowercase(),
-        hashed_password.to_string(),
         Utc::now(),
+        user.id,
+        session_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+        })),
+    ))
+}
+
+pub async fn set_pin(
+    State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(user): Extension<SignupModel>,
+    Json(body): Json<SetPinModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+    if !(4..=8).contains(&body.pin.len()) || !body.pin.chars().all(|c| c.is_ascii_digit()) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "success": false,
+                "message": "PIN must be 4 to 8 digits",
+            })),
+        ));
+    }
+
+    if !verify_password(&body.current_password, &user.password) {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(json!({
+                "success": false,
+                "message": "Current password is incorrect",
+            })),
+        ));
+    }
+
+    let pin_hash = hash_password(&body.pin)?;
+
+    sqlx::query!(
+        "UPDATE accounts SET pin_hash = $1, updated_at = $2 WHERE id = $3",
+        pin_hash,
         Utc::now(),
+        user.id,
     )
     .execute(&app_state.db)
     .await
     .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": format!("Database error: {}", e),
+            })),
+        )
+    })?;
+
+    audit_service::record(&app_state.db, client.event("pin_set").actor(user.id), AuditOutcome::Success).await;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+        })),
+    ))
+}
+
+pub async fn reset_password(
+    State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Json(body): Json<ResetPasswordModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+    let result = redeem_password_reset(&app_state, &body).await;
+
+    let event = client.event("password_reset").actor(result.as_ref().ok().copied());
+    audit_service::record_result(&app_state.db, event, &result).await;
+    result?;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+        })),
+    ))
+}
+
+/// Applies a password reset token and returns the account it belonged to.
+async fn redeem_password_reset(
+    app_state: &AppState,
+    body: &ResetPasswordModel,
+) -> Result<Uuid, (StatusCode, Json<Value>)> {
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": format!("Database error: {}", e),
+            })),
+        )
+    };
+    let invalid_token = || {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "success": false,
+                "message": "Invalid or expired reset token",
+            })),
+        )
+    };
+
+    let hashed_password = hash_password(&body.new_password)?;
+    let mut tx = app_state.db.begin().await.map_err(db_error)?;
+
+    let reset = sqlx::query!(
+        r#"
+            SELECT account_id, expires_at, used_at
+            FROM password_resets
+            WHERE token_hash = $1
+            FOR UPDATE
+        "#,
+        hash_secret(&body.reset_token),
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(db_error)?
+    .ok_or_else(invalid_token)?;
+
+    if reset.used_at.is_some() || reset.expires_at <= Utc::now() {
+        return Err(invalid_token());
+    }
+
+    sqlx::query!(
+        "UPDATE password_resets SET used_at = $1 WHERE token_hash = $2",
+        Utc::now(),
+        hash_secret(&body.reset_token),
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    sqlx::query!(
+        "UPDATE accounts SET password = $1, updated_at = $2 WHERE id = $3",
+        hashed_password,
+        Utc::now(),
+        reset.account_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    token_service::revoke_account_sessions(&mut *tx, reset.account_id).await?;
+    tx.commit().await.map_err(db_error)?;
+
+    Ok(reset.account_id)
+}
+
+pub async fn signup(
+    State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
+    headers: HeaderMap,
+    Json(credentials): Json<SignupInputModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+    let result = create_account(&app_state, &headers, &credentials).await;
+
+    let event = client.event("signup").username(&credentials.username);
+    audit_service::record_result(&app_state.db, event, &result).await;
+
+    result
+}
+
+async fn create_account(
+    app_state: &AppState,
+    headers: &HeaderMap,
+    credentials: &SignupInputModel,
+) -> Result<(StatusCode, Json<Value>), (StatusCode, Json<Value>)> {
+    let db_error = |e: sqlx::Error| {
         (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(json!({
@@ -151,12 +746,153 @@ pub async fn signup(
                 "message": e.to_string(),
             })),
         )
+    };
+    let forbidden = |message: &str| {
+        (
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "success": false,
+                "message": message,
+            })),
+        )
+    };
+    let username_taken = || {
+        (
+            StatusCode::CONFLICT,
+            Json(json!({
+                "success": false,
+                "message": "Username already taken",
+            })),
+        )
+    };
+
+    let mut tx = app_state.db.begin().await.map_err(db_error)?;
+
+    // Serialises signups so two requests cannot both claim the bootstrap owner.
+    sqlx::query!("LOCK TABLE accounts IN SHARE ROW EXCLUSIVE MODE")
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    let has_accounts = sqlx::query_scalar!(r#"SELECT EXISTS (SELECT 1 FROM accounts) AS "exists!""#)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    let mut invite_hash = None;
+    // The store the new account joins. The bootstrap owner needs none, as
+    // owners can act in every store.
+    let mut store_id = None;
+    let role = if !has_accounts {
+        Role::Owner
+    } else if headers.contains_key(header::AUTHORIZATION) {
+        let (manager, claims) = authenticate(app_state, headers)
+            .await
+            .map_err(|(status, Json(error))| (status, Json(json!(error))))?;
+
+        if manager.role < Role::Manager {
+            return Err(forbidden("Only managers can create accounts"));
+        }
+
+        let role = credentials.role.unwrap_or_default();
+        if manager.role != Role::Owner && role >= manager.role {
+            return Err(forbidden("You can only assign roles lower than your own"));
+        }
+        store_id = Some(claims.store_id);
+        role
+    } else if let Some(invite_code) = &credentials.invite_code {
+        let code_hash = hash_secret(invite_code);
+        let invite = sqlx::query!(
+            r#"
+                SELECT role AS "role: Role", store_id, expires_at, used_at
+                FROM invites
+                WHERE code_hash = $1
+                FOR UPDATE
+            "#,
+            code_hash,
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(db_error)?
+        .filter(|invite| invite.used_at.is_none() && invite.expires_at > Utc::now())
+        .ok_or_else(|| forbidden("Invalid or expired invite code"))?;
+
+        invite_hash = Some(code_hash);
+        store_id = Some(invite.store_id);
+        invite.role
+    } else {
+        return Err(forbidden("Signup requires an invite code"));
+    };
+
+    // Only authorised callers learn whether a username exists, and only they
+    // get a password hashed.
+    let username = credentials.username.to_ascii_lowercase();
+    let username_exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM accounts WHERE username = $1) AS "exists!""#,
+        username,
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    if username_exists {
+        return Err(username_taken());
+    }
+
+    let hashed_password = hash_password(&credentials.password)?;
+    let account_id = Uuid::new_v4();
+
+    sqlx::query!(
+        r#"
+            INSERT INTO accounts (id, full_name, username, password, role, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+        account_id,
+        credentials.full_name,
+        username,
+        hashed_password,
+        role as Role,
+        Utc::now(),
+        Utc::now(),
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| match &e {
+        sqlx::Error::Database(error) if error.is_unique_violation() => username_taken(),
+        _ => db_error(e),
     })?;
 
+    if let Some(store_id) = store_id {
+        sqlx::query!(
+            "INSERT INTO account_stores (account_id, store_id, created_at) VALUES ($1, $2, $3)",
+            account_id,
+            store_id,
+            Utc::now(),
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+    }
+
+    if let Some(code_hash) = invite_hash {
+        sqlx::query!(
+            "UPDATE invites SET used_at = $1, used_by = $2 WHERE code_hash = $3",
+            Utc::now(),
+            account_id,
+            code_hash,
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+    }
+
+    tx.commit().await.map_err(db_error)?;
+
     Ok((
         StatusCode::OK,
         Json(json!({
             "success": true,
+            "role": role,
         })),
     ))
-}
\ No newline at end of file
+}
@@ -1,16 +1,17 @@
 use std::sync::Arc;
-use axum::{extract::{Path, Query, State}, http::StatusCode, response::IntoResponse, Json};
+use axum::{extract::{Path, Query, State}, http::StatusCode, response::IntoResponse, Extension, Json};
 use serde_json::{json, Value};
 use uuid::Uuid;
 use chrono::Utc;
 
 use crate::{models::{categories_model::
-    CategoryModel, filter_model::FilterOptionsModel},
+    CategoryModel, filter_model::FilterOptionsModel, stores_model::ActiveStore},
     AppState
 };
 
 pub async fn get_all_categories(
     State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
     Query(filter_options): Query<FilterOptionsModel>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<Value>)>{
 
@@ -23,7 +24,9 @@ pub async fn get_all_categories(
         r#"
             SELECT COUNT(*)
             FROM categories
-        "#
+            WHERE store_id = $1
+        "#,
+        store.0,
     )
     .fetch_one(&app_state.db)
     .await
@@ -42,9 +45,11 @@ pub async fn get_all_categories(
         r#"
             SELECT category_id, category_name, created_at, updated_at
             FROM categories
-            OFFSET $1
-            LIMIT $2
+            WHERE store_id = $1
+            OFFSET $2
+            LIMIT $3
         "#,
+        store.0,
         offset,
         limit,
     )
@@ -76,6 +81,7 @@ pub async fn get_all_categories(
 
 pub async fn create_category(
     State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
     Json(category): Json<CategoryModel> 
 ) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
 
@@ -84,14 +90,15 @@ pub async fn create_category(
     let category = sqlx::query_as!(
         CategoryModel,
         r#"
-            INSERT INTO categories (category_id, category_name, created_at, updated_at)
-            VALUES ($1, $2, $3, $4)
-            RETURNING *
+            INSERT INTO categories (category_id, category_name, created_at, updated_at, store_id)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING category_id, category_name, created_at, updated_at
         "#,
         category_id,
         category.category_name,
         Utc::now(),
         Utc::now(),
+        store.0,
     )
     .fetch_one(&app_state.db)
     .await
@@ -114,19 +121,21 @@ pub async fn create_category(
 
 pub async fn update_category(
     State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
     Path(category_id): Path<String>,
     Json(update_category): Json<CategoryModel>
 ) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
 
-    sqlx::query!(
+    let updated = sqlx::query!(
         r#"
             UPDATE categories
             SET category_name = $1, created_at = $2
-            WHERE category_id = $3
+            WHERE category_id = $3 AND store_id = $4
         "#,
         update_category.category_name,
         Utc::now(),
         category_id,
+        store.0,
     )
         .execute(&app_state.db)
         .await
@@ -138,7 +147,12 @@ pub async fn update_category(
                     "message": e.to_string(),
                 })),
             )
-         })?;
+         })?
+        .rows_affected();
+
+    if updated == 0 {
+        return Err(category_not_found());
+    }
 
     Ok((
         StatusCode::OK,
@@ -150,15 +164,17 @@ pub async fn update_category(
 
 pub async fn delete_category(
     State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
     Path(category_id): Path<String>
 ) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
 
-    sqlx::query!(
+    let deleted = sqlx::query!(
         r#"
             DELETE FROM categories
-            WHERE category_id = $1
+            WHERE category_id = $1 AND store_id = $2
         "#,
-        category_id
+        category_id,
+        store.0,
     )
         .execute(&app_state.db)
         .await
@@ -170,7 +186,12 @@ pub async fn delete_category(
                     "message": e.to_string(),
                 })),
            )
-        })?;
+        })?
+        .rows_affected();
+
+    if deleted == 0 {
+        return Err(category_not_found());
+    }
     
     Ok((
         StatusCode::OK,
@@ -178,4 +199,14 @@ pub async fn delete_category(
             "success": true,
         })),
     ))
+}
+
+fn category_not_found() -> (StatusCode, Json<Value>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({
+            "success": false,
+            "message": "Category not found",
+        })),
+    )
 }
\ No newline at end of file
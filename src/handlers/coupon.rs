@@ -0,0 +1,367 @@
+use std::sync::Arc;
+use axum::{extract::{Path, Query, State}, http::StatusCode, response::IntoResponse, Extension, Json};
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+use uuid::Uuid;
+use chrono::Utc;
+
+use crate::{
+    models::{
+        coupons_model::{CouponModel, CreateCouponModel, DiscountType, UpdateCouponModel},
+        filter_model::FilterOptionsModel,
+        stores_model::ActiveStore,
+        transactions_model::TransactionInputModel},
+    services::{tax_service::round_money, transaction_service::price_items},
+    AppState
+};
+
+fn coupon_not_found() -> (StatusCode, Json<Value>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({
+            "success": false,
+            "message": "Coupon not found",
+        })),
+    )
+}
+
+fn bad_request(message: &str) -> (StatusCode, Json<Value>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(json!({
+            "success": false,
+            "message": message,
+        })),
+    )
+}
+
+/// Rejects limits below one and negative minimum baskets.
+fn check_limits(
+    max_uses: Option<i32>,
+    max_uses_per_customer: Option<i32>,
+    min_basket: Option<Decimal>,
+) -> Result<(), (StatusCode, Json<Value>)> {
+    if max_uses.is_some_and(|max_uses| max_uses < 1)
+        || max_uses_per_customer.is_some_and(|max_uses_per_customer| max_uses_per_customer < 1)
+    {
+        return Err(bad_request("Usage limits must be at least 1"));
+    }
+    if min_basket.is_some_and(|min_basket| min_basket < Decimal::ZERO) {
+        return Err(bad_request("min_basket must not be negative"));
+    }
+    Ok(())
+}
+
+pub async fn get_all_coupons(
+    State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
+    Query(filter_options): Query<FilterOptionsModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let limit = filter_options.limit.unwrap_or(10);
+    let offset = (filter_options.offset.unwrap_or(1) - 1) * limit;
+
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    };
+
+    let total_coupons: Option<i64> = sqlx::query_scalar!(
+        r#"
+            SELECT COUNT(*)
+            FROM coupons
+            WHERE store_id = $1
+        "#,
+        store.0,
+    )
+    .fetch_one(&app_state.db)
+    .await
+    .map_err(db_error)?;
+
+    let coupons = sqlx::query_as!(
+        CouponModel,
+        r#"
+            SELECT
+                coupon_id, code, discount_type AS "discount_type: DiscountType", discount_value, valid_from,
+                valid_until, max_uses, max_uses_per_customer, min_basket, is_active, times_used,
+                created_at, updated_at
+            FROM coupons
+            WHERE store_id = $1
+            ORDER BY code
+            OFFSET $2
+            LIMIT $3
+        "#,
+        store.0,
+        offset,
+        limit,
+    )
+    .fetch_all(&app_state.db)
+    .await
+    .map_err(db_error)?;
+
+    let json_response = json!({
+        "success": true,
+        "data": coupons,
+        "total": total_coupons,
+        "offset": offset,
+        "limit": limit,
+    });
+
+    Ok((
+        StatusCode::OK,
+        Json(json_response),
+    ))
+}
+
+/// Codes are case-insensitive and stored upper case.
+pub async fn create_coupon(
+    State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
+    Json(coupon): Json<CreateCouponModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let code = coupon.code.trim().to_uppercase();
+    if code.is_empty() {
+        return Err(bad_request("code must not be empty"));
+    }
+
+    let discount_value = match coupon.discount_type {
+        DiscountType::Percent => coupon.discount_value,
+        DiscountType::Fixed => round_money(coupon.discount_value),
+    };
+    if discount_value <= Decimal::ZERO
+        || (coupon.discount_type == DiscountType::Percent && discount_value > Decimal::ONE_HUNDRED)
+    {
+        return Err(bad_request("discount_value must be positive, and at most 100 for percent coupons"));
+    }
+
+    check_limits(coupon.max_uses, coupon.max_uses_per_customer, coupon.min_basket)?;
+
+    let coupon_id = data_encoding::BASE64URL_NOPAD.encode( Uuid::new_v4().as_bytes());
+
+    let coupon = sqlx::query_as!(
+        CouponModel,
+        r#"
+            INSERT INTO coupons (
+                coupon_id, store_id, code, discount_type, discount_value, valid_from, valid_until, max_uses,
+                max_uses_per_customer, min_basket, created_at, updated_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+            RETURNING
+                coupon_id, code, discount_type AS "discount_type: DiscountType", discount_value, valid_from,
+                valid_until, max_uses, max_uses_per_customer, min_basket, is_active, times_used,
+                created_at, updated_at
+        "#,
+        coupon_id,
+        store.0,
+        code,
+        coupon.discount_type as DiscountType,
+        discount_value,
+        coupon.valid_from,
+        coupon.valid_until,
+        coupon.max_uses,
+        coupon.max_uses_per_customer,
+        coupon.min_basket,
+        Utc::now(),
+        Utc::now(),
+    )
+    .fetch_one(&app_state.db)
+    .await
+    .map_err(|e| match &e {
+        sqlx::Error::Database(db_error) if db_error.is_unique_violation() => (
+            StatusCode::CONFLICT,
+            Json(json!({
+                "success": false,
+                "message": "A coupon with this code already exists",
+            })),
+        ),
+        _ => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        ),
+    })?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({
+            "success": true,
+            "data": coupon,
+        })),
+    ))
+}
+
+/// The code and discount cannot change once created, so redemptions keep
+/// meaning what they meant; deactivate the coupon and create a new one instead.
+pub async fn update_coupon(
+    State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
+    Path(coupon_id): Path<String>,
+    Json(coupon): Json<UpdateCouponModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    check_limits(coupon.max_uses, coupon.max_uses_per_customer, coupon.min_basket)?;
+
+    let coupon = sqlx::query_as!(
+        CouponModel,
+        r#"
+            UPDATE coupons
+            SET valid_from = COALESCE($1, valid_from),
+                valid_until = COALESCE($2, valid_until),
+                max_uses = COALESCE($3, max_uses),
+                max_uses_per_customer = COALESCE($4, max_uses_per_customer),
+                min_basket = COALESCE($5, min_basket),
+                is_active = COALESCE($6, is_active),
+                updated_at = $7
+            WHERE coupon_id = $8 AND store_id = $9
+            RETURNING
+                coupon_id, code, discount_type AS "discount_type: DiscountType", discount_value, valid_from,
+                valid_until, max_uses, max_uses_per_customer, min_basket, is_active, times_used,
+                created_at, updated_at
+        "#,
+        coupon.valid_from,
+        coupon.valid_until,
+        coupon.max_uses,
+        coupon.max_uses_per_customer,
+        coupon.min_basket,
+        coupon.is_active,
+        Utc::now(),
+        coupon_id,
+        store.0,
+    )
+    .fetch_optional(&app_state.db)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?
+    .ok_or_else(coupon_not_found)?;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+            "data": coupon,
+        })),
+    ))
+}
+
+/// Coupons that were redeemed stay on record; deactivate them instead.
+pub async fn delete_coupon(
+    State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
+    Path(coupon_id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    };
+
+    let redeemed = sqlx::query_scalar!(
+        r#"
+            SELECT EXISTS (
+                SELECT 1 FROM coupon_redemptions
+                JOIN coupons ON coupons.coupon_id = coupon_redemptions.coupon_id
+                WHERE coupon_redemptions.coupon_id = $1 AND coupons.store_id = $2
+            ) AS "redeemed!"
+        "#,
+        coupon_id,
+        store.0,
+    )
+    .fetch_one(&app_state.db)
+    .await
+    .map_err(db_error)?;
+
+    if redeemed {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(json!({
+                "success": false,
+                "message": "Coupon was redeemed and can only be deactivated",
+            })),
+        ));
+    }
+
+    let deleted = sqlx::query!(
+        r#"
+            DELETE FROM coupons
+            WHERE coupon_id = $1 AND store_id = $2
+        "#,
+        coupon_id,
+        store.0,
+    )
+    .execute(&app_state.db)
+    .await
+    .map_err(db_error)?
+    .rows_affected();
+
+    if deleted == 0 {
+        return Err(coupon_not_found());
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+        })),
+    ))
+}
+
+/// Prices a cart with `coupon_code` applied, exactly as recording the sale
+/// would, without recording anything or redeeming the coupon.
+pub async fn validate_coupon(
+    State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
+    Json(cart): Json<TransactionInputModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    if cart.coupon_code.is_none() {
+        return Err(bad_request("coupon_code is required"));
+    }
+
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    };
+
+    let mut tx = app_state.db.begin().await.map_err(db_error)?;
+    let sale = price_items(&mut tx, &store, &cart).await?;
+    tx.rollback().await.map_err(db_error)?;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+            "data": {
+                "coupon": sale.coupon,
+                "discounts": sale.discounts,
+                "discount_total": sale.discount_total,
+                "tax_total": sale.tax_total,
+                "total_price": sale.total_price,
+            },
+        })),
+    ))
+}
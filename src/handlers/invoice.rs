@@ -0,0 +1,212 @@
+use std::sync::Arc;
+use axum::{
+    extract::{Path, State},
+    http::{header::{CONTENT_DISPOSITION, CONTENT_TYPE}, StatusCode},
+    response::IntoResponse,
+    Extension,
+    Json};
+use chrono::Utc;
+use serde_json::{json, Value};
+
+use crate::{
+    models::{
+        invoices_model::{CreateInvoiceModel, InvoiceModel},
+        stores_model::ActiveStore,
+        transactions_model::TransactionType},
+    services::{
+        invoice_service::{invoice_code, render_invoice},
+        transaction_service::fetch_transaction},
+    AppState
+};
+
+/// Trims optional details and drops the empty ones.
+fn optional(text: Option<String>) -> Option<String> {
+    text.map(|text| text.trim().to_string()).filter(|text| !text.is_empty())
+}
+
+/// Issues the invoice of a sale with the next invoice number of the store.
+/// Each sale is invoiced at most once; the PDF can be downloaded again at any
+/// time.
+pub async fn create_invoice(
+    State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
+    Path(transaction_id): Path<String>,
+    Json(invoice): Json<CreateInvoiceModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let rejected = |status: StatusCode, message: &str| {
+        (
+            status,
+            Json(json!({
+                "success": false,
+                "message": message,
+            })),
+        )
+    };
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    };
+
+    let customer_name = invoice.customer_name.trim();
+    let billing_address = invoice.billing_address.trim();
+    if customer_name.is_empty() || billing_address.is_empty() {
+        return Err(rejected(StatusCode::BAD_REQUEST, "customer_name and billing_address are required"));
+    }
+
+    let mut tx = app_state.db.begin().await.map_err(db_error)?;
+
+    // Locking the sale makes a second request for the same invoice wait and
+    // then see the first one.
+    let transaction = sqlx::query!(
+        r#"
+            SELECT transaction_type AS "transaction_type: TransactionType", voided_at
+            FROM transactions
+            WHERE transaction_id = $1 AND store_id = $2
+            FOR UPDATE
+        "#,
+        transaction_id,
+        store.0,
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(db_error)?
+    .ok_or_else(|| rejected(StatusCode::NOT_FOUND, "Transaction not found"))?;
+
+    if transaction.transaction_type != TransactionType::Sale {
+        return Err(rejected(StatusCode::BAD_REQUEST, "Only sales can be invoiced"));
+    }
+    if transaction.voided_at.is_some() {
+        return Err(rejected(StatusCode::CONFLICT, "Voided sales cannot be invoiced"));
+    }
+
+    let invoiced = sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM invoices WHERE transaction_id = $1) AS "invoiced!""#,
+        transaction_id,
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    if invoiced {
+        return Err(rejected(StatusCode::CONFLICT, "Transaction was already invoiced"));
+    }
+
+    // The store row stays locked until commit, so numbers are handed out in
+    // order, and a rollback returns the number. The seller's details are
+    // copied onto the invoice from the same row.
+    let seller = sqlx::query!(
+        r#"
+            UPDATE stores
+            SET last_invoice_number = last_invoice_number + 1
+            WHERE store_id = $1
+            RETURNING last_invoice_number, store_name, address, tax_id
+        "#,
+        store.0,
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    let invoice = sqlx::query_as!(
+        InvoiceModel,
+        r#"
+            INSERT INTO invoices (
+                store_id, invoice_number, transaction_id, seller_name, seller_address, seller_tax_id, customer_name,
+                company_name, billing_address, customer_tax_id, customer_email, issued_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+            RETURNING
+                invoice_number, transaction_id, seller_name, seller_address, seller_tax_id, customer_name,
+                company_name, billing_address, customer_tax_id, customer_email, issued_at
+        "#,
+        store.0,
+        seller.last_invoice_number,
+        transaction_id,
+        seller.store_name,
+        seller.address,
+        seller.tax_id,
+        customer_name,
+        optional(invoice.company_name),
+        billing_address,
+        optional(invoice.customer_tax_id),
+        optional(invoice.customer_email),
+        Utc::now(),
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({
+            "success": true,
+            "data": invoice,
+        })),
+    ))
+}
+
+/// Renders the invoice of a sale as a PDF.
+pub async fn get_invoice(
+    State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
+    Path(transaction_id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    };
+
+    let invoice = sqlx::query_as!(
+        InvoiceModel,
+        r#"
+            SELECT
+                invoice_number, transaction_id, seller_name, seller_address, seller_tax_id, customer_name,
+                company_name, billing_address, customer_tax_id, customer_email, issued_at
+            FROM invoices
+            WHERE transaction_id = $1 AND store_id = $2
+        "#,
+        transaction_id,
+        store.0,
+    )
+    .fetch_optional(&app_state.db)
+    .await
+    .map_err(db_error)?
+    .ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "success": false,
+                "message": "Invoice not found",
+            })),
+        )
+    })?;
+
+    let transaction = fetch_transaction(&app_state.db, &store, &transaction_id).await?;
+
+    let pdf = render_invoice(&invoice, &transaction)?;
+
+    Ok((
+        [
+            (CONTENT_TYPE, "application/pdf".to_string()),
+            (
+                CONTENT_DISPOSITION,
+                format!("inline; filename=\"invoice-{}.pdf\"", invoice_code(invoice.invoice_number)),
+            ),
+        ],
+        pdf,
+    ))
+}
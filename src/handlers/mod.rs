@@ -1,4 +1,14 @@
 pub mod product;
 pub mod transaction;
 pub mod category;
-pub mod auth;
\ No newline at end of file
+pub mod auth;
+pub mod account;
+pub mod audit;
+pub mod register;
+pub mod api_key;
+pub mod totp;
+pub mod store;
+pub mod tax;
+pub mod promotion;
+pub mod coupon;
+pub mod invoice;
\ No newline at end of file
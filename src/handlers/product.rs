@@ -1,4 +1,4 @@
-use axum::{extract::{Multipart, Path, Query, State}, http::StatusCode, response::IntoResponse, Json};
+use axum::{extract::{Multipart, Path, Query, State}, http::StatusCode, response::IntoResponse, Extension, Json};
 use rust_decimal::Decimal;
 use serde_json::{json, Value};
 use uuid::Uuid;
@@ -7,14 +7,17 @@ use std::sync::Arc;
 
 use crate::{
     models::{
+        auth_model::{Role, SignupModel},
         filter_model::FilterOptionsModel,
-        products_model::{GetProductModel, PostProductModel}},
+        products_model::{GetProductModel, PostProductModel},
+        stores_model::ActiveStore},
     services::image_service::upload_image,
     AppState
 };
 
 pub async fn get_all_products(
     State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
     Query(filter_options): Query<FilterOptionsModel>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
 
@@ -27,7 +30,9 @@ pub async fn get_all_products(
         r#"
             SELECT COUNT(*)
             FROM products
-        "#
+            WHERE store_id = $1
+        "#,
+        store.0,
     )
     .fetch_one(&app_state.db)
     .await
@@ -45,14 +50,16 @@ pub async fn get_all_products(
         GetProductModel,
         r#"
             SELECT
-                product_id, product_name, price, stock, sku, category_name, product_image, products.created_at, products.updated_at
+                product_id, product_name, price, stock, sku, category_name, products.tax_class_id, product_image, products.is_active, products.created_at, products.updated_at
             FROM products
             LEFT JOIN categories
             ON products.category_id = categories.category_id
+            WHERE products.store_id = $1
             ORDER BY product_id
-            OFFSET $1
-            LIMIT $2
+            OFFSET $2
+            LIMIT $3
         "#,
+        store.0,
         offset,
         limit,
     )
@@ -100,6 +107,7 @@ pub async fn get_all_products(
 
 pub async fn get_product(
     State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
     Path(product_id): Path<String>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
 
@@ -107,15 +115,16 @@ pub async fn get_product(
         GetProductModel,
         r#"
             SELECT
-                product_id, product_name, price, stock, sku, category_name, product_image, products.created_at, products.updated_at
+                product_id, product_name, price, stock, sku, category_name, products.tax_class_id, product_image, products.is_active, products.created_at, products.updated_at
             FROM products
             LEFT JOIN categories
             ON products.category_id = categories.category_id
-            WHERE product_id = $1
+            WHERE product_id = $1 AND products.store_id = $2
         "#,
-        product_id
+        product_id,
+        store.0,
     )
-    .fetch_one(&app_state.db)
+    .fetch_optional(&app_state.db)
     .await
     .map_err(|e| {
         (
@@ -125,7 +134,8 @@ pub async fn get_product(
                 "message": e.to_string(),
             })),
         )
-    })?;
+    })?
+    .ok_or_else(product_not_found)?;
 
     if let Some(image) = &product.product_image {
         let presign_url = app_state.s3.presign_get(image, 86400, None).await.map_err(|_| {
@@ -149,6 +159,7 @@ pub async fn get_product(
 
 pub async fn create_product(
     State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
     mut multipart: Multipart,
 ) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
 
@@ -159,7 +170,9 @@ pub async fn create_product(
         stock: None,
         sku: None,
         category_id: None,
+        tax_class_id: None,
         product_image: None,
+        is_active: None,
         created_at: None,
         updated_at: None,
     };
@@ -191,9 +204,19 @@ pub async fn create_product(
                     product.category_id = Some(id_str);
                 }
             }
+            Some("tax_class_id") => {
+                if let Ok(id_str) = field.text().await {
+                    product.tax_class_id = Some(id_str);
+                }
+            }
             Some("product_image") => {
                 product.product_image = Some(upload_image(field, &app_state).await?);
             }
+            Some("is_active") => {
+                if let Ok(active_str) = field.text().await {
+                    product.is_active = active_str.parse::<bool>().ok();
+                }
+            }
             _ => {
                 return Err((
                     StatusCode::BAD_REQUEST,
@@ -205,14 +228,17 @@ pub async fn create_product(
         }
     }
 
+    ensure_category_in_store(&app_state, &store, product.category_id.as_deref()).await?;
+    ensure_tax_class_in_store(&app_state, &store, product.tax_class_id.as_deref()).await?;
+
     let product_id = data_encoding::BASE64URL_NOPAD.encode( Uuid::new_v4().as_bytes());
 
     let product = sqlx::query_as!(
         PostProductModel,
         r#"
-            INSERT INTO products (product_id, product_name, price, stock, sku, category_id, product_image, created_at, updated_at)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
-            RETURNING *
+            INSERT INTO products (product_id, product_name, price, stock, sku, category_id, tax_class_id, product_image, is_active, created_at, updated_at, store_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, COALESCE($9, TRUE), $10, $11, $12)
+            RETURNING product_id, product_name, price, stock, sku, category_id, tax_class_id, product_image, is_active, created_at, updated_at
         "#,
         product_id,
         product.product_name,
@@ -220,9 +246,12 @@ pub async fn create_product(
         product.stock,
         product.sku,
         product.category_id,
+        product.tax_class_id,
         product.product_image,
+        product.is_active,
         Utc::now(),
         Utc::now(),
+        store.0,
     )
     .fetch_one(&app_state.db)
     .await
@@ -247,6 +276,8 @@ pub async fn create_product(
 
 pub async fn update_product(
     State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<SignupModel>,
+    Extension(store): Extension<ActiveStore>,
     Path(product_id): Path<String>,
     mut multipart: Multipart,
 ) ->  Result<impl IntoResponse, (StatusCode, Json<Value>)> {
@@ -258,7 +289,9 @@ pub async fn update_product(
         stock: None,
         sku: None,
         category_id: None,
+        tax_class_id: None,
         product_image: None,
+        is_active: None,
         created_at: None,
         updated_at: None,
     };
@@ -271,6 +304,15 @@ pub async fn update_product(
                 }
             }
             Some("price") => {
+                if user.role < Role::Manager {
+                    return Err((
+                        StatusCode::FORBIDDEN,
+                        Json(json!({
+                            "success": false,
+                            "message": "Only managers can change product prices",
+                        })),
+                    ));
+                }
                 if let Ok(price_str) = field.text().await {
                     if let Ok(price) = price_str.parse::<Decimal>() {
                         update_product.price = Some(price);
@@ -278,6 +320,15 @@ pub async fn update_product(
                 }
             }
             Some("stock") => {
+                if user.role < Role::Manager {
+                    return Err((
+                        StatusCode::FORBIDDEN,
+                        Json(json!({
+                            "success": false,
+                            "message": "Only managers can set stock levels",
+                        })),
+                    ));
+                }
                 if let Ok(stock_str) = field.text().await {
                     if let Ok(stock) = stock_str.parse::<i32>() {
                         update_product.stock = Some(stock);
@@ -290,22 +341,64 @@ pub async fn update_product(
                 }
             }
             Some("category_id") => {
+                if user.role < Role::Manager {
+                    return Err((
+                        StatusCode::FORBIDDEN,
+                        Json(json!({
+                            "success": false,
+                            "message": "Only managers can move products to another category",
+                        })),
+                    ));
+                }
                 if let Ok(id_str) = field.text().await {
                     update_product.category_id = Some(id_str);
                 }
             }
+            Some("tax_class_id") => {
+                if user.role < Role::Manager {
+                    return Err((
+                        StatusCode::FORBIDDEN,
+                        Json(json!({
+                            "success": false,
+                            "message": "Only managers can change how products are taxed",
+                        })),
+                    ));
+                }
+                if let Ok(id_str) = field.text().await {
+                    update_product.tax_class_id = Some(id_str);
+                }
+            }
             Some("product_image") => {
                 if let Ok(image) = upload_image(field, &app_state).await {
                     update_product.product_image = Some(image)
                 }
             }
+            Some("is_active") => {
+                if user.role < Role::Manager {
+                    return Err((
+                        StatusCode::FORBIDDEN,
+                        Json(json!({
+                            "success": false,
+                            "message": "Only managers can take products on or off sale",
+                        })),
+                    ));
+                }
+                if let Ok(active_str) = field.text().await {
+                    if let Ok(is_active) = active_str.parse::<bool>() {
+                        update_product.is_active = Some(is_active);
+                    }
+                }
+            }
             _ => {
                 continue;
             }
         }
     }
 
-    sqlx::query!(
+    ensure_category_in_store(&app_state, &store, update_product.category_id.as_deref()).await?;
+    ensure_tax_class_in_store(&app_state, &store, update_product.tax_class_id.as_deref()).await?;
+
+    let updated = sqlx::query!(
         r#"
             UPDATE products
             SET
@@ -314,18 +407,23 @@ pub async fn update_product(
                 stock = COALESCE($3, stock),
                 sku = COALESCE($4, sku),
                 category_id = COALESCE($5, category_id),
-                product_image = COALESCE($6, product_image),
-                updated_at = COALESCE($7, updated_at)
-            WHERE product_id = $8
+                tax_class_id = COALESCE($6, tax_class_id),
+                product_image = COALESCE($7, product_image),
+                is_active = COALESCE($8, is_active),
+                updated_at = COALESCE($9, updated_at)
+            WHERE product_id = $10 AND store_id = $11
         "#,
         update_product.product_name,
         update_product.price,
         update_product.stock,
         update_product.sku,
         update_product.category_id,
+        update_product.tax_class_id,
         update_product.product_image,
+        update_product.is_active,
         Utc::now(),
         product_id,
+        store.0,
     )
     .execute(&app_state.db)
     .await
@@ -337,7 +435,12 @@ pub async fn update_product(
                 "message": e.to_string(),
             })),
         )
-    })?;
+    })?
+    .rows_affected();
+
+    if updated == 0 {
+        return Err(product_not_found());
+    }
 
     Ok((
         StatusCode::OK,
@@ -350,15 +453,17 @@ pub async fn update_product(
 
 pub async fn delete_product(
     State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
     Path(product_id): Path<String>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
 
-    sqlx::query!(
+    let deleted = sqlx::query!(
         r#"
             DELETE FROM products
-            WHERE product_id = $1
+            WHERE product_id = $1 AND store_id = $2
         "#,
         product_id,
+        store.0,
     )
         .execute(&app_state.db)
         .await
@@ -370,7 +475,12 @@ pub async fn delete_product(
                     "message": e.to_string(),
                 })),
             )
-        })?;
+        })?
+        .rows_affected();
+
+    if deleted == 0 {
+        return Err(product_not_found());
+    }
 
     Ok((
         StatusCode::OK,
@@ -379,3 +489,93 @@ pub async fn delete_product(
         })),
     ))
 }
+
+fn product_not_found() -> (StatusCode, Json<Value>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({
+            "success": false,
+            "message": "Product not found",
+        })),
+    )
+}
+
+/// Rejects a category that does not belong to the active store.
+async fn ensure_category_in_store(
+    app_state: &AppState,
+    store: &ActiveStore,
+    category_id: Option<&str>,
+) -> Result<(), (StatusCode, Json<Value>)> {
+    let Some(category_id) = category_id else {
+        return Ok(());
+    };
+
+    let exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM categories WHERE category_id = $1 AND store_id = $2) AS "exists!""#,
+        category_id,
+        store.0,
+    )
+    .fetch_one(&app_state.db)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?;
+
+    if !exists {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "success": false,
+                "message": "Category not found",
+            })),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Rejects a tax class that does not belong to the active store.
+async fn ensure_tax_class_in_store(
+    app_state: &AppState,
+    store: &ActiveStore,
+    tax_class_id: Option<&str>,
+) -> Result<(), (StatusCode, Json<Value>)> {
+    let Some(tax_class_id) = tax_class_id else {
+        return Ok(());
+    };
+
+    let exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM tax_classes WHERE tax_class_id = $1 AND store_id = $2) AS "exists!""#,
+        tax_class_id,
+        store.0,
+    )
+    .fetch_one(&app_state.db)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?;
+
+    if !exists {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "success": false,
+                "message": "Tax class not found",
+            })),
+        ));
+    }
+
+    Ok(())
+}
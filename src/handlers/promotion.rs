@@ -0,0 +1,366 @@
+use std::sync::Arc;
+use axum::{extract::{Path, Query, State}, http::StatusCode, response::IntoResponse, Extension, Json};
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+use uuid::Uuid;
+use chrono::Utc;
+
+use crate::{
+    models::{
+        filter_model::FilterOptionsModel,
+        promotions_model::{CreatePromotionModel, PromotionModel, PromotionType, UpdatePromotionModel},
+        stores_model::ActiveStore},
+    AppState
+};
+
+fn promotion_not_found() -> (StatusCode, Json<Value>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({
+            "success": false,
+            "message": "Promotion not found",
+        })),
+    )
+}
+
+fn bad_request(message: &str) -> (StatusCode, Json<Value>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(json!({
+            "success": false,
+            "message": message,
+        })),
+    )
+}
+
+fn check_discount_percent(discount_percent: Decimal) -> Result<(), (StatusCode, Json<Value>)> {
+    if discount_percent <= Decimal::ZERO || discount_percent > Decimal::ONE_HUNDRED {
+        return Err(bad_request("discount_percent must be between 0 and 100"));
+    }
+    Ok(())
+}
+
+/// Promotions of the active store, including inactive and expired ones.
+pub async fn get_all_promotions(
+    State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
+    Query(filter_options): Query<FilterOptionsModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let limit = filter_options.limit.unwrap_or(10);
+    let offset = (filter_options.offset.unwrap_or(1) - 1) * limit;
+
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    };
+
+    let total_promotions: Option<i64> = sqlx::query_scalar!(
+        r#"
+            SELECT COUNT(*)
+            FROM promotions
+            WHERE store_id = $1
+        "#,
+        store.0,
+    )
+    .fetch_one(&app_state.db)
+    .await
+    .map_err(db_error)?;
+
+    let promotions = sqlx::query_as!(
+        PromotionModel,
+        r#"
+            SELECT
+                promotion_id, promotion_name, promotion_type AS "promotion_type: PromotionType", product_id,
+                category_id, buy_quantity, get_quantity, min_quantity, discount_percent, starts_at, ends_at,
+                daily_start, daily_end, is_active, created_at, updated_at
+            FROM promotions
+            WHERE store_id = $1
+            ORDER BY created_at DESC, promotion_id
+            OFFSET $2
+            LIMIT $3
+        "#,
+        store.0,
+        offset,
+        limit,
+    )
+    .fetch_all(&app_state.db)
+    .await
+    .map_err(db_error)?;
+
+    let json_response = json!({
+        "success": true,
+        "data": promotions,
+        "total": total_promotions,
+        "offset": offset,
+        "limit": limit,
+    });
+
+    Ok((
+        StatusCode::OK,
+        Json(json_response),
+    ))
+}
+
+/// Only the fields the promotion type uses are kept: a product with
+/// `buy_quantity` and `get_quantity` for buy X get Y, a product with
+/// `min_quantity` for quantity breaks, and a category for category percent.
+pub async fn create_promotion(
+    State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
+    Json(promotion): Json<CreatePromotionModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    };
+
+    let (product_id, category_id, buy_quantity, get_quantity, min_quantity, discount_percent) =
+        match promotion.promotion_type {
+            PromotionType::BuyXGetY => {
+                let (Some(product_id), Some(buy_quantity), Some(get_quantity)) =
+                    (promotion.product_id, promotion.buy_quantity, promotion.get_quantity)
+                else {
+                    return Err(bad_request("buy_x_get_y needs a product_id, buy_quantity and get_quantity"));
+                };
+                if buy_quantity < 1 || get_quantity < 1 {
+                    return Err(bad_request("buy_quantity and get_quantity must be at least 1"));
+                }
+                let discount_percent = promotion.discount_percent.unwrap_or(Decimal::ONE_HUNDRED);
+                (Some(product_id), None, Some(buy_quantity), Some(get_quantity), None, discount_percent)
+            }
+            PromotionType::QuantityBreak => {
+                let (Some(product_id), Some(min_quantity), Some(discount_percent)) =
+                    (promotion.product_id, promotion.min_quantity, promotion.discount_percent)
+                else {
+                    return Err(bad_request("quantity_break needs a product_id, min_quantity and discount_percent"));
+                };
+                if min_quantity < 1 {
+                    return Err(bad_request("min_quantity must be at least 1"));
+                }
+                (Some(product_id), None, None, None, Some(min_quantity), discount_percent)
+            }
+            PromotionType::CategoryPercent => {
+                let (Some(category_id), Some(discount_percent)) = (promotion.category_id, promotion.discount_percent)
+                else {
+                    return Err(bad_request("category_percent needs a category_id and discount_percent"));
+                };
+                (None, Some(category_id), None, None, None, discount_percent)
+            }
+        };
+
+    check_discount_percent(discount_percent)?;
+
+    if promotion.daily_start.is_some() != promotion.daily_end.is_some() {
+        return Err(bad_request("daily_start and daily_end must be given together"));
+    }
+    if let (Some(starts_at), Some(ends_at)) = (promotion.starts_at, promotion.ends_at) {
+        if starts_at >= ends_at {
+            return Err(bad_request("starts_at must be before ends_at"));
+        }
+    }
+
+    let in_store = sqlx::query_scalar!(
+        r#"
+            SELECT (
+                ($1::TEXT IS NULL OR EXISTS (SELECT 1 FROM products WHERE product_id = $1 AND store_id = $3))
+                AND ($2::TEXT IS NULL OR EXISTS (SELECT 1 FROM categories WHERE category_id = $2 AND store_id = $3))
+            ) AS "in_store!"
+        "#,
+        product_id,
+        category_id,
+        store.0,
+    )
+    .fetch_one(&app_state.db)
+    .await
+    .map_err(db_error)?;
+
+    if !in_store {
+        return Err(bad_request(if product_id.is_some() { "Product not found" } else { "Category not found" }));
+    }
+
+    let promotion_id = data_encoding::BASE64URL_NOPAD.encode( Uuid::new_v4().as_bytes());
+
+    let promotion = sqlx::query_as!(
+        PromotionModel,
+        r#"
+            INSERT INTO promotions (
+                promotion_id, store_id, promotion_name, promotion_type, product_id, category_id, buy_quantity,
+                get_quantity, min_quantity, discount_percent, starts_at, ends_at, daily_start, daily_end, created_at,
+                updated_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)
+            RETURNING
+                promotion_id, promotion_name, promotion_type AS "promotion_type: PromotionType", product_id,
+                category_id, buy_quantity, get_quantity, min_quantity, discount_percent, starts_at, ends_at,
+                daily_start, daily_end, is_active, created_at, updated_at
+        "#,
+        promotion_id,
+        store.0,
+        promotion.promotion_name,
+        promotion.promotion_type as PromotionType,
+        product_id,
+        category_id,
+        buy_quantity,
+        get_quantity,
+        min_quantity,
+        discount_percent,
+        promotion.starts_at,
+        promotion.ends_at,
+        promotion.daily_start,
+        promotion.daily_end,
+        Utc::now(),
+        Utc::now(),
+    )
+    .fetch_one(&app_state.db)
+    .await
+    .map_err(db_error)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({
+            "success": true,
+            "data": promotion,
+        })),
+    ))
+}
+
+/// Changes take effect on the next sale; sales already recorded keep the
+/// discounts they were given.
+pub async fn update_promotion(
+    State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
+    Path(promotion_id): Path<String>,
+    Json(promotion): Json<UpdatePromotionModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    if let Some(discount_percent) = promotion.discount_percent {
+        check_discount_percent(discount_percent)?;
+    }
+
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    };
+
+    let mut tx = app_state.db.begin().await.map_err(db_error)?;
+
+    // Either end of the window can change on its own, so check it against
+    // the stored other end.
+    let current = sqlx::query!(
+        r#"
+            SELECT starts_at, ends_at
+            FROM promotions
+            WHERE promotion_id = $1 AND store_id = $2
+            FOR UPDATE
+        "#,
+        promotion_id,
+        store.0,
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(db_error)?
+    .ok_or_else(promotion_not_found)?;
+
+    if let (Some(starts_at), Some(ends_at)) =
+        (promotion.starts_at.or(current.starts_at), promotion.ends_at.or(current.ends_at))
+    {
+        if starts_at >= ends_at {
+            return Err(bad_request("starts_at must be before ends_at"));
+        }
+    }
+
+    let promotion = sqlx::query_as!(
+        PromotionModel,
+        r#"
+            UPDATE promotions
+            SET promotion_name = COALESCE($1, promotion_name),
+                discount_percent = COALESCE($2, discount_percent),
+                starts_at = COALESCE($3, starts_at),
+                ends_at = COALESCE($4, ends_at),
+                is_active = COALESCE($5, is_active),
+                updated_at = $6
+            WHERE promotion_id = $7 AND store_id = $8
+            RETURNING
+                promotion_id, promotion_name, promotion_type AS "promotion_type: PromotionType", product_id,
+                category_id, buy_quantity, get_quantity, min_quantity, discount_percent, starts_at, ends_at,
+                daily_start, daily_end, is_active, created_at, updated_at
+        "#,
+        promotion.promotion_name,
+        promotion.discount_percent,
+        promotion.starts_at,
+        promotion.ends_at,
+        promotion.is_active,
+        Utc::now(),
+        promotion_id,
+        store.0,
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+            "data": promotion,
+        })),
+    ))
+}
+
+pub async fn delete_promotion(
+    State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
+    Path(promotion_id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let deleted = sqlx::query!(
+        r#"
+            DELETE FROM promotions
+            WHERE promotion_id = $1 AND store_id = $2
+        "#,
+        promotion_id,
+        store.0,
+    )
+    .execute(&app_state.db)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?
+    .rows_affected();
+
+    if deleted == 0 {
+        return Err(promotion_not_found());
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+        })),
+    ))
+}
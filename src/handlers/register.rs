@@ -0,0 +1,231 @@
+use std::sync::Arc;
+use axum::{extract::{Path, Query, State}, http::StatusCode, response::IntoResponse, Extension, Json};
+use serde_json::{json, Value};
+use uuid::Uuid;
+use chrono::Utc;
+
+use crate::{
+    models::{
+        audit_model::AuditOutcome,
+        auth_model::SignupModel,
+        filter_model::FilterOptionsModel,
+        registers_model::RegisterModel,
+        stores_model::ActiveStore},
+    services::{
+        audit_service::{self, ClientInfo},
+        token_service::{generate_secret, hash_secret}},
+    AppState
+};
+
+pub async fn get_all_registers(
+    State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
+    Query(filter_options): Query<FilterOptionsModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let limit = filter_options.limit.unwrap_or(10);
+    let offset = (filter_options.offset.unwrap_or(1) - 1) * limit;
+
+    let total_registers: Option<i64> = sqlx::query_scalar!(
+        r#"
+            SELECT COUNT(*)
+            FROM registers
+            WHERE store_id = $1
+        "#,
+        store.0,
+    )
+    .fetch_one(&app_state.db)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success" : false,
+                "message" : e.to_string(),
+            })),
+        )
+    })?;
+
+    let registers = sqlx::query_as!(
+        RegisterModel,
+        r#"
+            SELECT register_id, register_number, register_name, is_active, created_at, updated_at
+            FROM registers
+            WHERE store_id = $1
+            ORDER BY register_name
+            OFFSET $2
+            LIMIT $3
+        "#,
+        store.0,
+        offset,
+        limit,
+    )
+    .fetch_all(&app_state.db)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?;
+
+    let json_response = json!({
+        "success": true,
+        "data": registers,
+        "total": total_registers,
+        "offset": offset,
+        "limit": limit,
+    });
+
+    Ok((
+        StatusCode::OK,
+        Json(json_response),
+    ))
+}
+
+/// The returned `register_key` is shown once and is what the terminal
+/// presents, together with a cashier's PIN, to `/api/pin-login`.
+pub async fn create_register(
+    State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(user): Extension<SignupModel>,
+    Extension(store): Extension<ActiveStore>,
+    Json(register): Json<RegisterModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let register_name = register.register_name.ok_or_else(|| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "success": false,
+                "message": "register_name is required",
+            })),
+        )
+    })?;
+
+    let register_id = data_encoding::BASE64URL_NOPAD.encode( Uuid::new_v4().as_bytes());
+    let register_key = generate_secret();
+
+    let register = sqlx::query_as!(
+        RegisterModel,
+        r#"
+            WITH numbered AS (
+                UPDATE stores
+                SET last_register_number = last_register_number + 1
+                WHERE store_id = $7
+                RETURNING last_register_number
+            )
+            INSERT INTO registers (
+                register_id, register_number, register_name, credential_hash, created_by, created_at, updated_at,
+                store_id
+            )
+            SELECT $1, last_register_number, $2, $3, $4, $5, $6, $7
+            FROM numbered
+            RETURNING register_id, register_number, register_name, is_active, created_at, updated_at
+        "#,
+        register_id,
+        register_name,
+        hash_secret(&register_key),
+        user.id,
+        Utc::now(),
+        Utc::now(),
+        store.0,
+    )
+    .fetch_one(&app_state.db)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?;
+
+    let event = client.event("register_create").actor(user.id).target(&register_id);
+    audit_service::record(&app_state.db, event, AuditOutcome::Success).await;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({
+            "success": true,
+            "data": register,
+            "register_key": register_key,
+        })),
+    ))
+}
+
+pub async fn revoke_register(
+    State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(user): Extension<SignupModel>,
+    Extension(store): Extension<ActiveStore>,
+    Path(register_id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    };
+
+    let mut tx = app_state.db.begin().await.map_err(db_error)?;
+
+    let revoked = sqlx::query!(
+        r#"
+            UPDATE registers
+            SET is_active = FALSE, updated_at = $1
+            WHERE register_id = $2 AND store_id = $3
+        "#,
+        Utc::now(),
+        register_id,
+        store.0,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(db_error)?
+    .rows_affected();
+
+    if revoked == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "success": false,
+                "message": "Register not found",
+            })),
+        ));
+    }
+
+    sqlx::query!(
+        r#"
+            UPDATE sessions
+            SET revoked_at = $1
+            WHERE register_id = $2 AND revoked_at IS NULL
+        "#,
+        Utc::now(),
+        register_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let event = client.event("register_revoke").actor(user.id).target(&register_id);
+    audit_service::record(&app_state.db, event, AuditOutcome::Success).await;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+        })),
+    ))
+}
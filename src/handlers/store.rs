@@ -0,0 +1,423 @@
+use std::sync::Arc;
+use axum::{extract::{Path, Query, State}, http::StatusCode, response::IntoResponse, Extension, Json};
+use serde_json::{json, Value};
+use uuid::Uuid;
+use chrono::Utc;
+
+use crate::{
+    models::{
+        audit_model::AuditOutcome,
+        auth_model::{Role, SignupModel, TokenClaims},
+        filter_model::FilterOptionsModel,
+        stores_model::{CreateStoreModel, StockPolicy, StoreMemberModel, StoreModel, UpdateStoreModel}},
+    services::{
+        audit_service::{self, ClientInfo},
+        store_service::resolve_session_store,
+        token_service::issue_session},
+    AppState
+};
+
+fn store_not_found() -> (StatusCode, Json<Value>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({
+            "success": false,
+            "message": "Store not found",
+        })),
+    )
+}
+
+/// Owners see every store; everyone else sees the stores they work in.
+pub async fn get_all_stores(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<SignupModel>,
+    Query(filter_options): Query<FilterOptionsModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let limit = filter_options.limit.unwrap_or(10);
+    let offset = (filter_options.offset.unwrap_or(1) - 1) * limit;
+
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    };
+
+    let total_stores: Option<i64> = sqlx::query_scalar!(
+        r#"
+            SELECT COUNT(*)
+            FROM stores
+            WHERE $1 = 'owner'::account_role OR EXISTS (
+                SELECT 1 FROM account_stores
+                WHERE account_stores.account_id = $2 AND account_stores.store_id = stores.store_id
+            )
+        "#,
+        user.role as Role,
+        user.id,
+    )
+    .fetch_one(&app_state.db)
+    .await
+    .map_err(db_error)?;
+
+    let stores = sqlx::query_as!(
+        StoreModel,
+        r#"
+            SELECT store_id, store_number, store_name, is_active, stock_policy AS "stock_policy: StockPolicy",
+                prices_include_tax, address, tax_id, receipt_header, receipt_footer, created_at, updated_at
+            FROM stores
+            WHERE $1 = 'owner'::account_role OR EXISTS (
+                SELECT 1 FROM account_stores
+                WHERE account_stores.account_id = $2 AND account_stores.store_id = stores.store_id
+            )
+            ORDER BY store_name
+            OFFSET $3
+            LIMIT $4
+        "#,
+        user.role as Role,
+        user.id,
+        offset,
+        limit,
+    )
+    .fetch_all(&app_state.db)
+    .await
+    .map_err(db_error)?;
+
+    let json_response = json!({
+        "success": true,
+        "data": stores,
+        "total": total_stores,
+        "offset": offset,
+        "limit": limit,
+    });
+
+    Ok((
+        StatusCode::OK,
+        Json(json_response),
+    ))
+}
+
+pub async fn create_store(
+    State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(user): Extension<SignupModel>,
+    Json(store): Json<CreateStoreModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let store_id = data_encoding::BASE64URL_NOPAD.encode( Uuid::new_v4().as_bytes());
+
+    let store = sqlx::query_as!(
+        StoreModel,
+        r#"
+            INSERT INTO stores (
+                store_id, store_name, stock_policy, prices_include_tax, address, tax_id, receipt_header,
+                receipt_footer, created_at, updated_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            RETURNING store_id, store_number, store_name, is_active, stock_policy AS "stock_policy: StockPolicy",
+                prices_include_tax, address, tax_id, receipt_header, receipt_footer, created_at, updated_at
+        "#,
+        store_id,
+        store.store_name,
+        store.stock_policy.unwrap_or_default() as StockPolicy,
+        store.prices_include_tax.unwrap_or(false),
+        store.address,
+        store.tax_id,
+        store.receipt_header,
+        store.receipt_footer,
+        Utc::now(),
+        Utc::now(),
+    )
+    .fetch_one(&app_state.db)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?;
+
+    let event = client.event("store_create").actor(user.id).target(&store_id);
+    audit_service::record(&app_state.db, event, AuditOutcome::Success).await;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({
+            "success": true,
+            "data": store,
+        })),
+    ))
+}
+
+/// Deactivating a store locks everyone out of it, owners included, until it
+/// is reactivated.
+pub async fn update_store(
+    State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(user): Extension<SignupModel>,
+    Path(store_id): Path<String>,
+    Json(store): Json<UpdateStoreModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let store = sqlx::query_as!(
+        StoreModel,
+        r#"
+            UPDATE stores
+            SET store_name = COALESCE($1, store_name),
+                is_active = COALESCE($2, is_active),
+                stock_policy = COALESCE($3, stock_policy),
+                prices_include_tax = COALESCE($4, prices_include_tax),
+                address = COALESCE($5, address),
+                tax_id = COALESCE($6, tax_id),
+                receipt_header = COALESCE($7, receipt_header),
+                receipt_footer = COALESCE($8, receipt_footer),
+                updated_at = $9
+            WHERE store_id = $10
+            RETURNING store_id, store_number, store_name, is_active, stock_policy AS "stock_policy: StockPolicy",
+                prices_include_tax, address, tax_id, receipt_header, receipt_footer, created_at, updated_at
+        "#,
+        store.store_name,
+        store.is_active,
+        store.stock_policy as Option<StockPolicy>,
+        store.prices_include_tax,
+        store.address,
+        store.tax_id,
+        store.receipt_header,
+        store.receipt_footer,
+        Utc::now(),
+        store_id,
+    )
+    .fetch_optional(&app_state.db)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?
+    .ok_or_else(store_not_found)?;
+
+    let event = client.event("store_update").actor(user.id).target(&store_id);
+    audit_service::record(&app_state.db, event, AuditOutcome::Success).await;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+            "data": store,
+        })),
+    ))
+}
+
+pub async fn add_store_member(
+    State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(user): Extension<SignupModel>,
+    Path(store_id): Path<String>,
+    Json(member): Json<StoreMemberModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let added = sqlx::query!(
+        r#"
+            INSERT INTO account_stores (account_id, store_id, created_at)
+            SELECT accounts.id, stores.store_id, $3
+            FROM accounts, stores
+            WHERE accounts.id = $1 AND stores.store_id = $2
+            ON CONFLICT DO NOTHING
+        "#,
+        member.account_id,
+        store_id,
+        Utc::now(),
+    )
+    .execute(&app_state.db)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?
+    .rows_affected();
+
+    if added == 0 {
+        let exists = sqlx::query_scalar!(
+            r#"
+                SELECT EXISTS (
+                    SELECT 1 FROM account_stores WHERE account_id = $1 AND store_id = $2
+                ) AS "exists!"
+            "#,
+            member.account_id,
+            store_id,
+        )
+        .fetch_one(&app_state.db)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({
+                    "success": false,
+                    "message": e.to_string(),
+                })),
+            )
+        })?;
+
+        if !exists {
+            return Err((
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "success": false,
+                    "message": "Store or account not found",
+                })),
+            ));
+        }
+    }
+
+    let event = client
+        .event("store_member_add")
+        .actor(user.id)
+        .target(member.account_id)
+        .detail(format!("store {}", store_id));
+    audit_service::record(&app_state.db, event, AuditOutcome::Success).await;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+        })),
+    ))
+}
+
+/// Removing a member also ends their sessions in that store.
+pub async fn remove_store_member(
+    State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(user): Extension<SignupModel>,
+    Path((store_id, account_id)): Path<(String, Uuid)>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    };
+
+    let mut tx = app_state.db.begin().await.map_err(db_error)?;
+
+    let removed = sqlx::query!(
+        "DELETE FROM account_stores WHERE account_id = $1 AND store_id = $2",
+        account_id,
+        store_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(db_error)?
+    .rows_affected();
+
+    if removed == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "success": false,
+                "message": "Account is not a member of this store",
+            })),
+        ));
+    }
+
+    sqlx::query!(
+        r#"
+            UPDATE sessions
+            SET revoked_at = $1
+            WHERE account_id = $2 AND store_id = $3 AND revoked_at IS NULL
+        "#,
+        Utc::now(),
+        account_id,
+        store_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let event = client
+        .event("store_member_remove")
+        .actor(user.id)
+        .target(account_id)
+        .detail(format!("store {}", store_id));
+    audit_service::record(&app_state.db, event, AuditOutcome::Success).await;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+        })),
+    ))
+}
+
+/// Starts a session in another store and ends the current one. Register
+/// sessions stay bound to their register's store.
+pub async fn switch_store(
+    State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(user): Extension<SignupModel>,
+    Extension(claims): Extension<TokenClaims>,
+    Path(store_id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let invalid_token = || {
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(json!({
+                "success": false,
+                "message": "Invalid token",
+            })),
+        )
+    };
+
+    let account_id = user.id.ok_or_else(invalid_token)?;
+    let session_id = Uuid::parse_str(&claims.sid).map_err(|_| invalid_token())?;
+
+    if claims.register_id.is_some() {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "success": false,
+                "message": "Register sessions cannot switch stores",
+            })),
+        ));
+    }
+
+    let store_id = resolve_session_store(&app_state.db, account_id, user.role, Some(&store_id)).await?;
+    // Replacing the current session means it can only be switched once.
+    let tokens = issue_session(&app_state, account_id, user.role, &store_id, Some(session_id)).await?;
+
+    let event = client.event("store_switch").actor(Some(account_id)).target(&store_id);
+    audit_service::record(&app_state.db, event, AuditOutcome::Success).await;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+            "store_id": store_id,
+            "token": tokens.token,
+            "refresh_token": tokens.refresh_token,
+            "expires_in": tokens.expires_in,
+        })),
+    ))
+}
@@ -0,0 +1,356 @@
+use std::sync::Arc;
+use axum::{extract::{Path, Query, State}, http::StatusCode, response::IntoResponse, Extension, Json};
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+use uuid::Uuid;
+use chrono::Utc;
+
+use crate::{
+    models::{
+        filter_model::FilterOptionsModel,
+        stores_model::ActiveStore,
+        taxes_model::{CreateTaxRateModel, TaxClassInputModel, TaxClassModel, TaxRateModel}},
+    AppState
+};
+
+fn tax_class_not_found() -> (StatusCode, Json<Value>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({
+            "success": false,
+            "message": "Tax class not found",
+        })),
+    )
+}
+
+/// Tax classes of the active store with their rates.
+pub async fn get_all_tax_classes(
+    State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
+    Query(filter_options): Query<FilterOptionsModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let limit = filter_options.limit.unwrap_or(10);
+    let offset = (filter_options.offset.unwrap_or(1) - 1) * limit;
+
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    };
+
+    let total_tax_classes: Option<i64> = sqlx::query_scalar!(
+        r#"
+            SELECT COUNT(*)
+            FROM tax_classes
+            WHERE store_id = $1
+        "#,
+        store.0,
+    )
+    .fetch_one(&app_state.db)
+    .await
+    .map_err(db_error)?;
+
+    let tax_classes = sqlx::query_as!(
+        TaxClassModel,
+        r#"
+            SELECT
+                tax_class_id, class_name, created_at, updated_at,
+                COALESCE((
+                    SELECT jsonb_agg(jsonb_build_object(
+                        'tax_rate_id', tax_rate_id,
+                        'rate_name', rate_name,
+                        'rate', rate::TEXT
+                    ) ORDER BY created_at, tax_rate_id)
+                    FROM tax_rates
+                    WHERE tax_rates.tax_class_id = tax_classes.tax_class_id
+                ), '[]') AS "rates!"
+            FROM tax_classes
+            WHERE store_id = $1
+            ORDER BY class_name
+            OFFSET $2
+            LIMIT $3
+        "#,
+        store.0,
+        offset,
+        limit,
+    )
+    .fetch_all(&app_state.db)
+    .await
+    .map_err(db_error)?;
+
+    let json_response = json!({
+        "success": true,
+        "data": tax_classes,
+        "total": total_tax_classes,
+        "offset": offset,
+        "limit": limit,
+    });
+
+    Ok((
+        StatusCode::OK,
+        Json(json_response),
+    ))
+}
+
+pub async fn create_tax_class(
+    State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
+    Json(tax_class): Json<TaxClassInputModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let tax_class_id = data_encoding::BASE64URL_NOPAD.encode( Uuid::new_v4().as_bytes());
+
+    let tax_class = sqlx::query_as!(
+        TaxClassModel,
+        r#"
+            INSERT INTO tax_classes (tax_class_id, store_id, class_name, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING tax_class_id, class_name, '[]'::JSONB AS "rates!", created_at, updated_at
+        "#,
+        tax_class_id,
+        store.0,
+        tax_class.class_name,
+        Utc::now(),
+        Utc::now(),
+    )
+    .fetch_one(&app_state.db)
+    .await
+    .map_err(class_name_taken)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({
+            "success": true,
+            "data": tax_class,
+        })),
+    ))
+}
+
+pub async fn update_tax_class(
+    State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
+    Path(tax_class_id): Path<String>,
+    Json(tax_class): Json<TaxClassInputModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let updated = sqlx::query!(
+        r#"
+            UPDATE tax_classes
+            SET class_name = $1, updated_at = $2
+            WHERE tax_class_id = $3 AND store_id = $4
+        "#,
+        tax_class.class_name,
+        Utc::now(),
+        tax_class_id,
+        store.0,
+    )
+    .execute(&app_state.db)
+    .await
+    .map_err(class_name_taken)?
+    .rows_affected();
+
+    if updated == 0 {
+        return Err(tax_class_not_found());
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+        })),
+    ))
+}
+
+/// Classes still assigned to products cannot be deleted.
+pub async fn delete_tax_class(
+    State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
+    Path(tax_class_id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    };
+
+    let in_use = sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM products WHERE tax_class_id = $1 AND store_id = $2) AS "in_use!""#,
+        tax_class_id,
+        store.0,
+    )
+    .fetch_one(&app_state.db)
+    .await
+    .map_err(db_error)?;
+
+    if in_use {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(json!({
+                "success": false,
+                "message": "Tax class is still assigned to products",
+            })),
+        ));
+    }
+
+    let deleted = sqlx::query!(
+        r#"
+            DELETE FROM tax_classes
+            WHERE tax_class_id = $1 AND store_id = $2
+        "#,
+        tax_class_id,
+        store.0,
+    )
+    .execute(&app_state.db)
+    .await
+    .map_err(db_error)?
+    .rows_affected();
+
+    if deleted == 0 {
+        return Err(tax_class_not_found());
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+        })),
+    ))
+}
+
+/// Adds a rate to a class. Rates only affect sales recorded afterwards; past
+/// transactions keep the rates they were taxed with.
+pub async fn create_tax_rate(
+    State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
+    Path(tax_class_id): Path<String>,
+    Json(tax_rate): Json<CreateTaxRateModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    if tax_rate.rate < Decimal::ZERO || tax_rate.rate > Decimal::ONE_HUNDRED {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "success": false,
+                "message": "rate must be a percentage between 0 and 100",
+            })),
+        ));
+    }
+
+    let tax_rate_id = data_encoding::BASE64URL_NOPAD.encode( Uuid::new_v4().as_bytes());
+
+    let tax_rate = sqlx::query_as!(
+        TaxRateModel,
+        r#"
+            INSERT INTO tax_rates (tax_rate_id, tax_class_id, rate_name, rate, created_at)
+            SELECT $1, tax_class_id, $2, $3, $4
+            FROM tax_classes
+            WHERE tax_class_id = $5 AND store_id = $6
+            RETURNING tax_rate_id, rate_name, rate
+        "#,
+        tax_rate_id,
+        tax_rate.rate_name,
+        tax_rate.rate,
+        Utc::now(),
+        tax_class_id,
+        store.0,
+    )
+    .fetch_optional(&app_state.db)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?
+    .ok_or_else(tax_class_not_found)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({
+            "success": true,
+            "data": tax_rate,
+        })),
+    ))
+}
+
+pub async fn delete_tax_rate(
+    State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
+    Path((tax_class_id, tax_rate_id)): Path<(String, String)>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let deleted = sqlx::query!(
+        r#"
+            DELETE FROM tax_rates
+            USING tax_classes
+            WHERE tax_rates.tax_rate_id = $1
+                AND tax_rates.tax_class_id = $2
+                AND tax_classes.tax_class_id = tax_rates.tax_class_id
+                AND tax_classes.store_id = $3
+        "#,
+        tax_rate_id,
+        tax_class_id,
+        store.0,
+    )
+    .execute(&app_state.db)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?
+    .rows_affected();
+
+    if deleted == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "success": false,
+                "message": "Tax rate not found",
+            })),
+        ));
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+        })),
+    ))
+}
+
+fn class_name_taken(e: sqlx::Error) -> (StatusCode, Json<Value>) {
+    match &e {
+        sqlx::Error::Database(db_error) if db_error.is_unique_violation() => (
+            StatusCode::CONFLICT,
+            Json(json!({
+                "success": false,
+                "message": "A tax class with this name already exists",
+            })),
+        ),
+        _ => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        ),
+    }
+}
@@ -0,0 +1,253 @@
+use std::sync::Arc;
+use axum::{extract::{Path, State}, http::StatusCode, response::IntoResponse, Extension, Json};
+use serde_json::{json, Value};
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        audit_model::AuditOutcome,
+        auth_model::{
+            ConfirmTotpModel, DisableTotpModel, EnrollTotpModel, Role, RolePolicyModel, SignupModel,
+            UpdateRolePolicyModel}},
+    services::{audit_service::{self, ClientInfo}, password_service::verify_password, totp_service},
+    AppState
+};
+
+/// The authenticated account, re-checked with its password, since a stolen
+/// access token alone must not be enough to change the second factor.
+fn reauthenticate(user: &SignupModel, current_password: &str) -> Result<Uuid, (StatusCode, Json<Value>)> {
+    let account_id = user.id.ok_or_else(|| {
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(json!({
+                "success": false,
+                "message": "Account not found",
+            })),
+        )
+    })?;
+
+    if !verify_password(current_password, &user.password) {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(json!({
+                "success": false,
+                "message": "Current password is incorrect",
+            })),
+        ));
+    }
+
+    Ok(account_id)
+}
+
+pub async fn enroll_totp(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<SignupModel>,
+    Json(body): Json<EnrollTotpModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let account_id = reauthenticate(&user, &body.current_password)?;
+    let totp_enabled = sqlx::query_scalar!("SELECT totp_enabled FROM accounts WHERE id = $1", account_id)
+        .fetch_one(&app_state.db)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({
+                    "success": false,
+                    "message": e.to_string(),
+                })),
+            )
+        })?;
+
+    if totp_enabled {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(json!({
+                "success": false,
+                "message": "Two-factor authentication is already enabled",
+            })),
+        ));
+    }
+
+    let (secret, otpauth_uri) = totp_service::begin_enrollment(&app_state.db, account_id, &user.username).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+            "secret": secret,
+            "otpauth_uri": otpauth_uri,
+        })),
+    ))
+}
+
+pub async fn confirm_totp(
+    State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(user): Extension<SignupModel>,
+    Json(body): Json<ConfirmTotpModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let account_id = reauthenticate(&user, &body.current_password)?;
+    let recovery_codes = totp_service::confirm_enrollment(&app_state.db, account_id, &body.code).await?;
+
+    let event = client.event("totp_enable").actor(user.id);
+    audit_service::record(&app_state.db, event, AuditOutcome::Success).await;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+            "recovery_codes": recovery_codes,
+        })),
+    ))
+}
+
+pub async fn disable_totp(
+    State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(user): Extension<SignupModel>,
+    Json(body): Json<DisableTotpModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let account_id = reauthenticate(&user, &body.current_password)?;
+
+    if totp_service::is_required_for(&app_state.db, user.role).await? {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "success": false,
+                "message": "Two-factor authentication is required for your role",
+            })),
+        ));
+    }
+
+    totp_service::verify_second_factor(&app_state.db, account_id, &body.code).await?;
+
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    };
+
+    let mut tx = app_state.db.begin().await.map_err(db_error)?;
+
+    sqlx::query!(
+        r#"
+            UPDATE accounts
+            SET totp_secret = NULL, totp_pending_secret = NULL, totp_enabled = FALSE, totp_last_step = NULL,
+                updated_at = $1
+            WHERE id = $2
+        "#,
+        Utc::now(),
+        account_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    sqlx::query!("DELETE FROM recovery_codes WHERE account_id = $1", account_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let event = client.event("totp_disable").actor(user.id);
+    audit_service::record(&app_state.db, event, AuditOutcome::Success).await;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+        })),
+    ))
+}
+
+pub async fn get_role_policies(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let policies = sqlx::query_as!(
+        RolePolicyModel,
+        r#"
+            SELECT role AS "role: Role", require_totp, updated_by, updated_at
+            FROM role_policies
+            ORDER BY role
+        "#
+    )
+    .fetch_all(&app_state.db)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+            "data": policies,
+        })),
+    ))
+}
+
+pub async fn update_role_policy(
+    State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(user): Extension<SignupModel>,
+    Path(role): Path<Role>,
+    Json(policy): Json<UpdateRolePolicyModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let policy = sqlx::query_as!(
+        RolePolicyModel,
+        r#"
+            INSERT INTO role_policies (role, require_totp, updated_by, updated_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (role) DO UPDATE
+            SET require_totp = EXCLUDED.require_totp, updated_by = EXCLUDED.updated_by, updated_at = EXCLUDED.updated_at
+            RETURNING role AS "role: Role", require_totp, updated_by, updated_at
+        "#,
+        role as Role,
+        policy.require_totp,
+        user.id,
+        Utc::now(),
+    )
+    .fetch_one(&app_state.db)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?;
+
+    let event = client
+        .event("role_policy_update")
+        .actor(user.id)
+        .target(role.as_str())
+        .detail(format!("require_totp: {}", policy.require_totp));
+    audit_service::record(&app_state.db, event, AuditOutcome::Success).await;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+            "data": policy,
+        })),
+    ))
+}
@@ -1,32 +1,55 @@
-use std::sync::Arc;
-use axum::{extract::{Query, State}, http::StatusCode, response::IntoResponse, Json};
+use std::{collections::BTreeMap, sync::Arc};
+use axum::{extract::{Path, Query, State}, http::{header::CONTENT_TYPE, StatusCode}, response::IntoResponse, Extension, Json};
 use chrono::Utc;
-use rust_decimal::{prelude::FromPrimitive, Decimal};
+use rust_decimal::Decimal;
 use serde_json::{json, Value};
 use uuid::Uuid;
 use crate::{
     models::{
-        filter_model::FilterOptionsModel,
-        transactions_model::{TransactionInputModel, TransactionModel}},
+        audit_model::AuditOutcome,
+        auth_model::{Role, SignupModel, TokenClaims},
+        promotions_model::DiscountSource,
+        receipts_model::{ReceiptFormat, ReceiptOptionsModel, ReceiptStoreModel},
+        stores_model::ActiveStore,
+        transactions_model::{
+            LineTax, RefundInputModel, TransactionFilterModel, TransactionInputModel, TransactionItem,
+            TransactionStoreModel, TransactionType}},
+    services::{
+        audit_service::{self, ClientInfo},
+        coupon_service::{redeem_coupon, release_coupon},
+        receipt_service::{render_escpos, render_html, render_text},
+        tax_service::round_money,
+        transaction_service::{
+            change_due, decrement_stock, fetch_transaction, insert_discounts, insert_payments,
+            insert_transaction_items, load_transactions, next_receipt_number, price_items, receipt_number_pattern,
+            return_to_stock}},
     AppState
 };
 
 
 pub async fn get_all_transactions(
     State(app_state): State<Arc<AppState>>,
-    Query(filter_options): Query<FilterOptionsModel>,
+    Extension(store): Extension<ActiveStore>,
+    Query(filter_options): Query<TransactionFilterModel>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
 
     // let Query(opts) = filter_options.unwrap_or_default();
 
     let limit = filter_options.limit.unwrap_or(20);
     let offset = (filter_options.offset.unwrap_or(1) - 1) * limit;
+    let receipt_number = filter_options.receipt_number.as_deref().map(str::trim);
 
     let total_transactions: Option<i64> = sqlx::query_scalar!(
         r#"
             SELECT COUNT(*)
             FROM transactions
-        "#
+            WHERE store_id = $1
+                AND ($2::TEXT IS NULL OR receipt_number LIKE $2)
+                AND ($3::UUID IS NULL OR cashier_id = $3)
+        "#,
+        store.0,
+        receipt_number.map(receipt_number_pattern),
+        filter_options.cashier_id,
     )
     .fetch_one(&app_state.db)
     .await
@@ -40,27 +63,16 @@ pub async fn get_all_transactions(
         )
     })?;
     
-    let transactions = sqlx::query_as!(
-        TransactionModel,
-        r#"
-            SELECT * FROM transactions
-            OFFSET $1
-            LIMIT $2
-        "#,
+    let transactions = load_transactions(
+        &app_state.db,
+        &store,
+        None,
+        receipt_number,
+        filter_options.cashier_id,
         offset,
         limit,
     )
-        .fetch_all(&app_state.db)
-        .await
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                    "succes": false,
-                    "message": e.to_string(),
-                })),
-            )
-        })?;
+    .await?;
     
     let json_response = json!({
         "succes": true,
@@ -76,63 +88,616 @@ pub async fn get_all_transactions(
     ))
 }
 
+/// A single sale with its lines and the store it was rung up in, for
+/// reprinting and lookups at the register.
+pub async fn get_transaction(
+    State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
+    Path(transaction_id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let transaction = fetch_transaction(&app_state.db, &store, &transaction_id).await?;
+
+    let store = sqlx::query_as!(
+        TransactionStoreModel,
+        "SELECT store_id, store_name FROM stores WHERE store_id = $1",
+        store.0,
+    )
+    .fetch_one(&app_state.db)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+            "data": transaction,
+            "store": store,
+        })),
+    ))
+}
+
+/// Renders the receipt of a transaction as plain text, ESC/POS commands or
+/// HTML, 42 columns wide unless `width` is 48.
+pub async fn get_receipt(
+    State(app_state): State<Arc<AppState>>,
+    Extension(store): Extension<ActiveStore>,
+    Path(transaction_id): Path<String>,
+    Query(options): Query<ReceiptOptionsModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let width = options.width.unwrap_or(42);
+    if width != 42 && width != 48 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "success": false,
+                "message": "width must be 42 or 48",
+            })),
+        ));
+    }
+
+    let transaction = fetch_transaction(&app_state.db, &store, &transaction_id).await?;
+
+    let store = sqlx::query_as!(
+        ReceiptStoreModel,
+        r#"
+            SELECT store_name, address, tax_id, receipt_header, receipt_footer
+            FROM stores
+            WHERE store_id = $1
+        "#,
+        store.0,
+    )
+    .fetch_one(&app_state.db)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?;
+
+    let response = match options.format.unwrap_or_default() {
+        ReceiptFormat::Text => (
+            [(CONTENT_TYPE, "text/plain; charset=utf-8")],
+            render_text(&transaction, &store, width)?,
+        )
+            .into_response(),
+        ReceiptFormat::Escpos => (
+            [(CONTENT_TYPE, "application/octet-stream")],
+            render_escpos(&transaction, &store, width)?,
+        )
+            .into_response(),
+        ReceiptFormat::Html => (
+            [(CONTENT_TYPE, "text/html; charset=utf-8")],
+            render_html(&transaction, &store, width)?,
+        )
+            .into_response(),
+    };
+
+    Ok(response)
+}
+
+/// The session a request was made in. API keys have none.
+fn session_of(claims: &Option<Extension<TokenClaims>>) -> Option<Uuid> {
+    claims.as_ref().and_then(|Extension(claims)| Uuid::parse_str(&claims.sid).ok())
+}
+
+/// The register a request was made at, for sessions opened with a register
+/// key.
+fn register_of(claims: &Option<Extension<TokenClaims>>) -> Option<&str> {
+    claims.as_ref().and_then(|Extension(claims)| claims.register_id.as_deref())
+}
+
 pub async fn create_transaction(
     State(app_state): State<Arc<AppState>>,
+    Extension(user): Extension<SignupModel>,
+    Extension(store): Extension<ActiveStore>,
+    claims: Option<Extension<TokenClaims>>,
     Json(transactions): Json<TransactionInputModel>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
 
-    // let total_price = transactions.total_price;
-    let transaction_items = json!(transactions.transaction_items);
+    // Promotions and coupons are set up by managers; discounts made up at
+    // the till need one too.
+    let manual_discount = transactions.discount.is_some()
+        || transactions.transaction_items.iter().any(|item| item.discount.is_some());
+    if manual_discount && user.role < Role::Manager {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "success": false,
+                "message": "Only managers can give manual discounts",
+            })),
+        ));
+    }
 
-    let item_count = transaction_items
-        .as_array()
-        .map(|arr| arr.len())
-        .unwrap_or(0);
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    };
 
-    // let total_price = transactions.transaction_items.iter().fold(Decimal::ZERO, |acc, item| {
-    //     acc + (Decimal::from_f64(item.price).unwrap() * Decimal::from(item.quantity))
-    // });
+    let mut tx = app_state.db.begin().await.map_err(db_error)?;
 
-    let total_price = transactions.transaction_items.iter().fold(Decimal::ZERO, |acc, item| {
-        if let Some(price) = Decimal::from_f64(item.price) {
-            acc + (price * Decimal::from(item.quantity))
-        } else {
-            acc
-        }
-    });
+    let sale = price_items(&mut tx, &store, &transactions).await?;
+    let change_due = change_due(sale.total_price, &transactions.payments)?;
+    let (stock_levels, warnings) = decrement_stock(&mut tx, &store, &sale.items).await?;
+    let item_count = sale.items.len();
 
     let transaction_id = data_encoding::BASE64URL_NOPAD.encode( Uuid::new_v4().as_bytes());
+    let receipt_number = next_receipt_number(&mut tx, &store, register_of(&claims)).await?;
 
-    let result = sqlx::query_as!(
-        TransactionModel,
+    sqlx::query!(
         r#"
-            INSERT INTO transactions (transaction_id, transaction_date, total_price, transaction_items, item_count)
-            VALUES ($1, $2, $3, $4, $5)
-            RETURNING *
+            INSERT INTO transactions (
+                transaction_id, receipt_number, transaction_date, total_price, discount_total, tax_total,
+                prices_include_tax, change_due, item_count, store_id, session_id, cashier_id, register_id
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
         "#,
         transaction_id,
+        receipt_number,
         Utc::now(),
-        total_price,
-        transaction_items,
+        sale.total_price,
+        sale.discount_total,
+        sale.tax_total,
+        sale.prices_include_tax,
+        change_due,
         item_count as i32,
+        store.0,
+        session_of(&claims),
+        user.id,
+        register_of(&claims),
     )
-        .fetch_all(&app_state.db)
+        .execute(&mut *tx)
         .await
-        .map_err(|e|{
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                    "success": false,
-                    "message": e.to_string(),
-                })),
-            )
-        })?;
+        .map_err(db_error)?;
+
+    insert_transaction_items(&mut tx, &transaction_id, &sale.items).await?;
+    insert_discounts(&mut tx, &transaction_id, &sale.discounts).await?;
+    if let Some(coupon) = &sale.coupon {
+        let amount = sale
+            .discounts
+            .iter()
+            .filter(|discount| discount.discount_source == DiscountSource::Coupon)
+            .map(|discount| discount.amount)
+            .sum();
+        redeem_coupon(&mut tx, &coupon.coupon_id, &transaction_id, transactions.customer_ref.as_deref(), amount).await?;
+    }
+    insert_payments(&mut tx, &transaction_id, &transactions.payments).await?;
+
+    let result = fetch_transaction(&mut *tx, &store, &transaction_id).await?;
+
+    tx.commit().await.map_err(db_error)?;
     
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+            "data": [result],
+            "change_due": change_due,
+            "stock_levels": stock_levels,
+            "warnings": warnings,
+        })),
+    ))
+}
+
+/// The part of a line's `amount` that belongs to `quantity` of its
+/// `sold` units, given `already` were refunded before. Shares are rounded to
+/// cents and the last refund takes whatever is left, so they always add up.
+fn refund_share(amount: Decimal, sold: i32, already: i32, quantity: i32) -> Decimal {
+    if sold == 0 {
+        return Decimal::ZERO;
+    }
+    let refunded_up_to = |units: i32| round_money(amount * Decimal::from(units) / Decimal::from(sold));
+    refunded_up_to(already + quantity) - refunded_up_to(already)
+}
+
+/// Returns some or all of a sale's lines. The refund is recorded as a new
+/// transaction with negative quantities and total, and the returned units go
+/// back on stock unless `restock` is false.
+pub async fn refund_transaction(
+    State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(user): Extension<SignupModel>,
+    Extension(store): Extension<ActiveStore>,
+    claims: Option<Extension<TokenClaims>>,
+    Path(transaction_id): Path<String>,
+    Json(refund): Json<RefundInputModel>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    };
+    let bad_request = |message: String| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "success": false,
+                "message": message,
+            })),
+        )
+    };
+
+    let mut tx = app_state.db.begin().await.map_err(db_error)?;
+
+    // Locking the sale serializes refunds against it, so two refunds cannot
+    // both pass the quantity check below.
+    let original = sqlx::query!(
+        r#"
+            SELECT transaction_type AS "transaction_type: TransactionType", voided_at, prices_include_tax
+            FROM transactions
+            WHERE transaction_id = $1 AND store_id = $2
+            FOR UPDATE
+        "#,
+        transaction_id,
+        store.0,
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(db_error)?
+    .ok_or_else(transaction_not_found)?;
+
+    if original.transaction_type != TransactionType::Sale {
+        return Err(bad_request("Only sales can be refunded".to_string()));
+    }
+    if original.voided_at.is_some() {
+        return Err(bad_request("This sale was voided".to_string()));
+    }
+
+    let lines = sqlx::query!(
+        r#"
+            SELECT
+                line_number, product_id, product_name, product_category, sku, price, quantity, line_discount, line_tax,
+                line_total,
+                COALESCE((
+                    SELECT -SUM(refund_items.quantity)
+                    FROM transaction_items AS refund_items
+                    JOIN transactions AS refunds ON refunds.transaction_id = refund_items.transaction_id
+                    WHERE refunds.original_transaction_id = transaction_items.transaction_id
+                        AND refund_items.original_line_number = transaction_items.line_number
+                ), 0)::INT AS "refunded!"
+            FROM transaction_items
+            WHERE transaction_id = $1
+            ORDER BY line_number
+        "#,
+        transaction_id,
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    let line_taxes = sqlx::query!(
+        r#"
+            SELECT line_number, tax_rate_id, rate_name, rate, taxable_amount, tax_amount
+            FROM transaction_item_taxes
+            WHERE transaction_id = $1
+            ORDER BY line_number, tax_number
+        "#,
+        transaction_id,
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    let mut requested: BTreeMap<i32, i32> = BTreeMap::new();
+    match &refund.items {
+        Some(items) => {
+            for item in items {
+                let quantity = i32::try_from(item.quantity)
+                    .ok()
+                    .filter(|quantity| *quantity > 0)
+                    .ok_or_else(|| bad_request(format!("Invalid quantity for line {}", item.line_number)))?;
+                let total = requested.entry(item.line_number).or_default();
+                *total = total.saturating_add(quantity);
+            }
+        }
+        None => {
+            for line in &lines {
+                if line.quantity > line.refunded {
+                    requested.insert(line.line_number, line.quantity - line.refunded);
+                }
+            }
+        }
+    }
+
+    if requested.is_empty() {
+        return Err(bad_request("Nothing left to refund on this sale".to_string()));
+    }
+
+    let mut refund_items = Vec::with_capacity(requested.len());
+    let mut total_price = Decimal::ZERO;
+    let mut discount_total = Decimal::ZERO;
+    let mut tax_total = Decimal::ZERO;
+
+    for (line_number, quantity) in requested {
+        let line = lines
+            .iter()
+            .find(|line| line.line_number == line_number)
+            .ok_or_else(|| bad_request(format!("Line {} not found on this sale", line_number)))?;
+
+        let remaining = line.quantity - line.refunded;
+        if quantity > remaining {
+            return Err(bad_request(format!(
+                "Cannot refund {} of line {}, only {} left",
+                quantity, line_number, remaining,
+            )));
+        }
+
+        let share = |amount: Decimal| -refund_share(amount, line.quantity, line.refunded, quantity);
+
+        let taxes: Vec<LineTax> = line_taxes
+            .iter()
+            .filter(|tax| tax.line_number == line_number)
+            .map(|tax| LineTax {
+                tax_rate_id: tax.tax_rate_id.clone(),
+                rate_name: tax.rate_name.clone(),
+                rate: tax.rate,
+                taxable_amount: share(tax.taxable_amount),
+                tax_amount: share(tax.tax_amount),
+            })
+            .collect();
+        let line_tax = if taxes.is_empty() {
+            share(line.line_tax)
+        } else {
+            taxes.iter().map(|tax| tax.tax_amount).sum()
+        };
+        let line_discount = share(line.line_discount);
+        let line_total = share(line.line_total);
+        total_price += line_total;
+        discount_total += line_discount;
+        tax_total += line_tax;
+
+        refund_items.push(TransactionItem {
+            product_id: line.product_id.clone(),
+            product_name: line.product_name.clone(),
+            product_category: line.product_category.clone(),
+            sku: line.sku.clone(),
+            quantity: -quantity,
+            price: line.price,
+            line_discount,
+            line_tax,
+            line_total,
+            original_line_number: Some(line_number),
+            taxes,
+        });
+    }
+
+    let stock_levels = if refund.restock.unwrap_or(true) {
+        let returned = refund_items
+            .iter()
+            .filter_map(|item| Some((item.product_id.as_deref()?, -item.quantity)));
+        return_to_stock(&mut tx, &store, returned).await?
+    } else {
+        Vec::new()
+    };
+
+    let refund_id = data_encoding::BASE64URL_NOPAD.encode( Uuid::new_v4().as_bytes());
+    let receipt_number = next_receipt_number(&mut tx, &store, register_of(&claims)).await?;
+
+    sqlx::query!(
+        r#"
+            INSERT INTO transactions (
+                transaction_id, receipt_number, transaction_type, original_transaction_id, transaction_date,
+                total_price, discount_total, tax_total, prices_include_tax, item_count, store_id, session_id, reason,
+                cashier_id, register_id
+            )
+            VALUES ($1, $2, 'refund', $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+        "#,
+        refund_id,
+        receipt_number,
+        transaction_id,
+        Utc::now(),
+        total_price,
+        discount_total,
+        tax_total,
+        original.prices_include_tax,
+        refund_items.len() as i32,
+        store.0,
+        session_of(&claims),
+        refund.reason,
+        user.id,
+        register_of(&claims),
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    insert_transaction_items(&mut tx, &refund_id, &refund_items).await?;
+
+    let result = fetch_transaction(&mut *tx, &store, &refund_id).await?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let event = client
+        .event("transaction_refund")
+        .actor(user.id)
+        .target(&transaction_id)
+        .detail(format!("refund {} of {}", refund_id, total_price));
+    audit_service::record(&app_state.db, event, AuditOutcome::Success).await;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({
+            "success": true,
+            "data": result,
+            "stock_levels": stock_levels,
+        })),
+    ))
+}
+
+/// Cancels a sale rung up in the current session, for mistakes caught
+/// before the customer leaves. Everything sold goes back on stock. Sales that
+/// were partly refunded, or made in another session, need a refund instead.
+pub async fn void_transaction(
+    State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Extension(user): Extension<SignupModel>,
+    Extension(store): Extension<ActiveStore>,
+    claims: Option<Extension<TokenClaims>>,
+    Path(transaction_id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<Value>)> {
+
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    };
+    let rejected = |status: StatusCode, message: &str| {
+        (
+            status,
+            Json(json!({
+                "success": false,
+                "message": message,
+            })),
+        )
+    };
+
+    let mut tx = app_state.db.begin().await.map_err(db_error)?;
+
+    let sale = sqlx::query!(
+        r#"
+            SELECT
+                transaction_type AS "transaction_type: TransactionType", session_id, voided_at,
+                EXISTS (
+                    SELECT 1 FROM transactions AS refunds WHERE refunds.original_transaction_id = transactions.transaction_id
+                ) AS "refunded!",
+                EXISTS (
+                    SELECT 1 FROM invoices WHERE invoices.transaction_id = transactions.transaction_id
+                ) AS "invoiced!"
+            FROM transactions
+            WHERE transaction_id = $1 AND store_id = $2
+            FOR UPDATE
+        "#,
+        transaction_id,
+        store.0,
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(db_error)?
+    .ok_or_else(transaction_not_found)?;
+
+    if sale.transaction_type != TransactionType::Sale {
+        return Err(rejected(StatusCode::BAD_REQUEST, "Only sales can be voided"));
+    }
+    if sale.voided_at.is_some() {
+        return Err(rejected(StatusCode::BAD_REQUEST, "This sale was already voided"));
+    }
+    if sale.refunded {
+        return Err(rejected(StatusCode::BAD_REQUEST, "This sale was refunded and can no longer be voided"));
+    }
+    // An issued invoice cannot be taken back, so the sale has to be refunded.
+    if sale.invoiced {
+        return Err(rejected(StatusCode::CONFLICT, "This sale was invoiced and can only be refunded"));
+    }
+    if sale.session_id.is_none() || sale.session_id != session_of(&claims) {
+        return Err(rejected(StatusCode::FORBIDDEN, "Only sales from your current session can be voided"));
+    }
+
+    sqlx::query!(
+        r#"
+            UPDATE transactions
+            SET voided_at = $1, voided_by = $2
+            WHERE transaction_id = $3
+        "#,
+        Utc::now(),
+        user.id,
+        transaction_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    release_coupon(&mut tx, &transaction_id).await?;
+
+    let sold_items = sqlx::query!(
+        "SELECT product_id, quantity FROM transaction_items WHERE transaction_id = $1",
+        transaction_id,
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    let returned = sold_items
+        .iter()
+        .filter_map(|item| Some((item.product_id.as_deref()?, item.quantity)));
+    let stock_levels = return_to_stock(&mut tx, &store, returned).await?;
+    let result = fetch_transaction(&mut *tx, &store, &transaction_id).await?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let event = client.event("transaction_void").actor(user.id).target(&transaction_id);
+    audit_service::record(&app_state.db, event, AuditOutcome::Success).await;
+
     Ok((
         StatusCode::OK,
         Json(json!({
             "success": true,
             "data": result,
+            "stock_levels": stock_levels,
         })),
     ))
-}
\ No newline at end of file
+}
+
+fn transaction_not_found() -> (StatusCode, Json<Value>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({
+            "success": false,
+            "message": "Transaction not found",
+        })),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn money(amount: &str) -> Decimal {
+        amount.parse().unwrap()
+    }
+
+    #[test]
+    fn partial_refunds_add_up_to_the_line_amount() {
+        // 10.00 over three units cannot be split evenly in cents.
+        let shares: Vec<Decimal> = (0..3).map(|already| refund_share(money("10.00"), 3, already, 1)).collect();
+        assert_eq!(shares, [money("3.33"), money("3.34"), money("3.33")]);
+        assert_eq!(shares.iter().sum::<Decimal>(), money("10.00"));
+
+        // Refunding one unit and then the remaining two comes to the same.
+        let first = refund_share(money("10.00"), 3, 0, 1);
+        let rest = refund_share(money("10.00"), 3, 1, 2);
+        assert_eq!(first + rest, money("10.00"));
+        assert_eq!(refund_share(money("10.00"), 3, 0, 3), money("10.00"));
+    }
+
+    #[test]
+    fn refunds_nothing_of_a_line_without_units() {
+        assert_eq!(refund_share(money("10.00"), 0, 0, 1), Decimal::ZERO);
+    }
+}
@@ -1,8 +1,9 @@
 use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt};
-use std::{error::Error, sync::Arc};
+use std::{error::Error, net::SocketAddr, sync::Arc};
 use config::init_config;
 use routes::app_router;
 use s3::Bucket;
+use services::{jwt_keys::JwtKeys, login_throttle::LoginThrottle};
 use sqlx::PgPool;
 use tokio::net::TcpListener;
 
@@ -16,8 +17,9 @@ mod services;
 #[derive(Clone)]
 pub struct AppState {
     pub db: PgPool,
-    pub env: String,
+    pub jwt: Arc<JwtKeys>,
     pub s3: Box<Bucket>,
+    pub login_throttle: Arc<LoginThrottle>,
 }
 
 #[tokio::main]
@@ -36,13 +38,14 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     let app_state = Arc::new(AppState {
         db: config.pool.clone(),
-        env: config.jwt.clone(),
+        jwt: Arc::new(config.jwt),
         s3: config.s3.clone(),
+        login_throttle: Arc::new(LoginThrottle::new()),
     });
 
     let app = app_router(app_state);
 
-    axum::serve(listener, app)
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
     .with_graceful_shutdown(services::shutdown_service::shutdown_signal())
     .await
     .expect("Error serving application");
@@ -1,14 +1,22 @@
 use std::sync::Arc;
 use axum::{
-    body::Body, extract::State, http::{header, Request, StatusCode}, middleware::Next, response::IntoResponse, Json
+    body::Body, extract::{OriginalUri, State}, http::{header, HeaderMap, Method, Request, StatusCode}, middleware::Next, response::{IntoResponse, Response}, Extension, Json
 };
-use jsonwebtoken::{decode, DecodingKey, Validation};
+use chrono::Utc;
 use serde::Serialize;
+use uuid::Uuid;
 
 use crate::{
-    models::auth_model::{SignupModel, TokenClaims}, AppState
+    models::{
+        audit_model::AuditOutcome,
+        auth_model::{Role, SignupModel, TokenClaims},
+        stores_model::ActiveStore},
+    services::{audit_service::{self, AuditEvent, ClientInfo}, store_service, token_service::hash_secret},
+    AppState
 };
 
+pub const API_KEY_HEADER: &str = "x-api-key";
+
 #[derive(Debug, Serialize)]
 pub struct ErrorResponse {
     pub success: bool,
@@ -21,8 +29,42 @@ pub async fn auth(
     next: Next,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
 
-    let token = req
-        .headers()
+    let (user, claims) = match authenticate_token(&app_state, req.headers()).await {
+        Ok(authenticated) => authenticated,
+        Err((account_id, error)) => {
+            // Anyone can send a made up token, so only rejections of a token
+            // issued to a known account are worth an audit row.
+            if account_id.is_some() {
+                let event = rejection_event(&req, "access_denied", account_id, &error.1.message);
+                audit_service::record(&app_state.db, event, AuditOutcome::Failure).await;
+            }
+            return Err(error);
+        }
+    };
+
+    req.extensions_mut().insert(ActiveStore(claims.store_id.clone()));
+    req.extensions_mut().insert(user);
+    req.extensions_mut().insert(claims);
+    Ok(next.run(req).await)
+}
+
+/// Resolves the Bearer token in `headers` to its account and claims. Used by
+/// `auth` and by handlers where being logged in is optional.
+pub async fn authenticate(
+    app_state: &AppState,
+    headers: &HeaderMap,
+) -> Result<(SignupModel, TokenClaims), (StatusCode, Json<ErrorResponse>)> {
+    authenticate_token(app_state, headers).await.map_err(|(_, error)| error)
+}
+
+/// Like `authenticate`, but a rejection also names the account the token was
+/// issued to, once its signature checked out and the account exists.
+async fn authenticate_token(
+    app_state: &AppState,
+    headers: &HeaderMap,
+) -> Result<(SignupModel, TokenClaims), (Option<Uuid>, (StatusCode, Json<ErrorResponse>))> {
+
+    let token = headers
         .get(header::AUTHORIZATION)
         .and_then(|auth_header| auth_header.to_str().ok())
         .and_then(|auth_value| auth_value.strip_prefix("Bearer "))
@@ -31,32 +73,235 @@ pub async fn auth(
                 success: false,
                 message: "You are not logged in, please provide token".to_string(),
             };
-            (StatusCode::UNAUTHORIZED, Json(json_error))
+            (None, (StatusCode::UNAUTHORIZED, Json(json_error)))
         })?;
 
-    let claims = decode::<TokenClaims>(
-        &token,
-        &DecodingKey::from_secret(app_state.env.as_ref()),
-        &Validation::default(),
-    )
-    .map_err(|_| {
+    let claims = app_state.jwt.decode::<TokenClaims>(token, None).map_err(|_| {
         let json_error = ErrorResponse {
             success: false,
             message: "Invalid token".to_string(),
         };
-        (StatusCode::UNAUTHORIZED, Json(json_error))
-    })?
-    .claims;
+        (None, (StatusCode::UNAUTHORIZED, Json(json_error)))
+    })?;
 
-    let user_id = uuid::Uuid::parse_str(&claims.sub).map_err(|_| {
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| {
         let json_error = ErrorResponse {
             success: false,
             message: "Invalid token".to_string(),
         };
-        (StatusCode::UNAUTHORIZED, Json(json_error))
+        (None, (StatusCode::UNAUTHORIZED, Json(json_error)))
     })?;
 
-    let user = sqlx::query_as!(SignupModel, "SELECT * FROM accounts WHERE id = $1", user_id)
+    let user = fetch_user(app_state, user_id).await.map_err(|error| (None, error))?;
+
+    let mut user = user.ok_or_else(|| {
+        let json_error = ErrorResponse {
+            success: false,
+            message: "The user belonging to this token no longer exists".to_string(),
+        };
+        (None, (StatusCode::UNAUTHORIZED, Json(json_error)))
+    })?;
+
+    // Store access follows the account's own role, but a token never grants
+    // more than its claims, e.g. register sessions stay at cashier level even
+    // for a manager's account.
+    let account_role = user.role;
+    user.role = user.role.min(claims.role);
+
+    if user.is_active != Some(true) {
+        let json_error = ErrorResponse {
+            success: false,
+            message: "This account has been deactivated".to_string(),
+        };
+        return Err((Some(user_id), (StatusCode::UNAUTHORIZED, Json(json_error))));
+    }
+
+    let session_id = Uuid::parse_str(&claims.sid).map_err(|_| {
+        let json_error = ErrorResponse {
+            success: false,
+            message: "Invalid token".to_string(),
+        };
+        (Some(user_id), (StatusCode::UNAUTHORIZED, Json(json_error)))
+    })?;
+
+    // The session must be open and its account still allowed in its store.
+    let session_active = sqlx::query_scalar!(
+        r#"
+            SELECT revoked_at IS NULL AS "active!"
+            FROM sessions
+            WHERE session_id = $1 AND account_id = $2 AND store_id = $3
+        "#,
+        session_id,
+        user_id,
+        claims.store_id,
+    )
+    .fetch_optional(&app_state.db)
+    .await
+    .map_err(|e| {
+        let json_error = ErrorResponse {
+            success: false,
+            message: format!("Error fetching session from database: {}", e),
+        };
+        (None, (StatusCode::INTERNAL_SERVER_ERROR, Json(json_error)))
+    })?
+    .unwrap_or(false);
+
+    if !session_active {
+        let json_error = ErrorResponse {
+            success: false,
+            message: "Your session has ended, please log in again".to_string(),
+        };
+        return Err((Some(user_id), (StatusCode::UNAUTHORIZED, Json(json_error))));
+    }
+
+    let store_allowed = store_service::can_access(&app_state.db, user_id, account_role, &claims.store_id)
+        .await
+        .map_err(|e| {
+            let json_error = ErrorResponse {
+                success: false,
+                message: format!("Error fetching store from database: {}", e),
+            };
+            (None, (StatusCode::INTERNAL_SERVER_ERROR, Json(json_error)))
+        })?;
+
+    if !store_allowed {
+        let json_error = ErrorResponse {
+            success: false,
+            message: "You no longer have access to this store".to_string(),
+        };
+        return Err((Some(user_id), (StatusCode::FORBIDDEN, Json(json_error))));
+    }
+
+    Ok((user, claims))
+}
+
+
+/// Like `auth`, but also accepts an `X-API-Key` header. The key must hold
+/// `<resource>:read` for GET requests and `<resource>:write` otherwise, and
+/// acts with the role of the account that created it.
+pub async fn auth_or_api_key(
+    State((app_state, resource)): State<(Arc<AppState>, &'static str)>,
+    mut req: Request<Body>,
+    next: Next,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+
+    let Some(api_key) = req
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned)
+    else {
+        return auth(State(app_state), req, next)
+            .await
+            .map(IntoResponse::into_response);
+    };
+
+    let (user, store_id) = match authenticate_api_key(&app_state, &api_key, req.method(), resource).await {
+        Ok(authenticated) => authenticated,
+        Err((account_id, error)) => {
+            if account_id.is_some() {
+                let event = rejection_event(&req, "access_denied", account_id, &error.1.message);
+                audit_service::record(&app_state.db, event, AuditOutcome::Failure).await;
+            }
+            return Err(error);
+        }
+    };
+
+    req.extensions_mut().insert(ActiveStore(store_id));
+    req.extensions_mut().insert(user);
+    Ok(next.run(req).await)
+}
+
+/// Resolves an `X-API-Key` value to the account that created it and the
+/// store the key belongs to, provided the key holds the scope `method` needs
+/// on `resource`. Rejections of a genuine key name the key's creator.
+async fn authenticate_api_key(
+    app_state: &AppState,
+    api_key: &str,
+    method: &Method,
+    resource: &str,
+) -> Result<(SignupModel, String), (Option<Uuid>, (StatusCode, Json<ErrorResponse>))> {
+
+    let invalid_key = |account_id: Option<Uuid>| {
+        let json_error = ErrorResponse {
+            success: false,
+            message: "Invalid API key".to_string(),
+        };
+        (account_id, (StatusCode::UNAUTHORIZED, Json(json_error)))
+    };
+
+    let (key_id, _) = api_key.split_once('.').ok_or_else(|| invalid_key(None))?;
+
+    let key = sqlx::query!(
+        r#"
+            UPDATE api_keys
+            SET last_used_at = $1
+            WHERE key_id = $2 AND key_hash = $3 AND revoked_at IS NULL
+            RETURNING scopes, created_by, store_id
+        "#,
+        Utc::now(),
+        key_id,
+        hash_secret(api_key),
+    )
+    .fetch_optional(&app_state.db)
+    .await
+    .map_err(|e| {
+        let json_error = ErrorResponse {
+            success: false,
+            message: format!("Error fetching API key from database: {}", e),
+        };
+        (None, (StatusCode::INTERNAL_SERVER_ERROR, Json(json_error)))
+    })?
+    .ok_or_else(|| invalid_key(None))?;
+
+    let access = if matches!(*method, Method::GET | Method::HEAD) { "read" } else { "write" };
+    let required_scope = format!("{}:{}", resource, access);
+
+    if !key.scopes.contains(&required_scope) {
+        let json_error = ErrorResponse {
+            success: false,
+            message: format!("This API key is missing the {} scope", required_scope),
+        };
+        return Err((Some(key.created_by), (StatusCode::FORBIDDEN, Json(json_error))));
+    }
+
+    let user = fetch_user(app_state, key.created_by)
+        .await
+        .map_err(|error| (None, error))?
+        .filter(|user| user.is_active == Some(true))
+        .ok_or_else(|| invalid_key(Some(key.created_by)))?;
+
+    // A key stops working once its creator loses access to the key's store.
+    let store_allowed = store_service::can_access(&app_state.db, key.created_by, user.role, &key.store_id)
+        .await
+        .map_err(|e| {
+            let json_error = ErrorResponse {
+                success: false,
+                message: format!("Error fetching store from database: {}", e),
+            };
+            (None, (StatusCode::INTERNAL_SERVER_ERROR, Json(json_error)))
+        })?;
+
+    if !store_allowed {
+        return Err(invalid_key(Some(key.created_by)));
+    }
+
+    Ok((user, key.store_id))
+}
+
+async fn fetch_user(
+    app_state: &AppState,
+    user_id: Uuid,
+) -> Result<Option<SignupModel>, (StatusCode, Json<ErrorResponse>)> {
+    sqlx::query_as!(
+        SignupModel,
+        r#"
+            SELECT id, full_name, username, password, role AS "role: Role", is_active, created_at, updated_at
+            FROM accounts
+            WHERE id = $1
+        "#,
+        user_id,
+    )
         .fetch_optional(&app_state.db)
         .await
         .map_err(|e| {
@@ -65,16 +310,46 @@ pub async fn auth(
                 message: format!("Error fetching user from database: {}", e),
             };
             (StatusCode::INTERNAL_SERVER_ERROR, Json(json_error))
-        })?;
+        })
+}
+
+/// Rejects the request unless the account attached by `auth` holds at least
+/// `min_role`. Must be layered inside `auth`.
+pub async fn require_role(
+    State((app_state, min_role)): State<(Arc<AppState>, Role)>,
+    Extension(user): Extension<SignupModel>,
+    req: Request<Body>,
+    next: Next,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
 
-    let user = user.ok_or_else(|| {
+    if user.role < min_role {
         let json_error = ErrorResponse {
             success: false,
-            message: "The user belonging to this token no longer exists".to_string(),
+            message: "You do not have permission to perform this action".to_string(),
         };
-        (StatusCode::UNAUTHORIZED, Json(json_error))
-    })?;
+        let event = rejection_event(&req, "permission_denied", user.id, &json_error.message);
+        audit_service::record(&app_state.db, event, AuditOutcome::Failure).await;
+        return Err((StatusCode::FORBIDDEN, Json(json_error)));
+    }
 
-    req.extensions_mut().insert(user);
     Ok(next.run(req).await)
 }
+
+fn rejection_event(
+    req: &Request<Body>,
+    event_type: &'static str,
+    actor_id: Option<Uuid>,
+    message: &str,
+) -> AuditEvent {
+    // Nested routers strip their prefix from `uri()`, so prefer the original.
+    let path = req
+        .extensions()
+        .get::<OriginalUri>()
+        .map_or_else(|| req.uri().path(), |OriginalUri(uri)| uri.path());
+
+    ClientInfo::from_parts(req.extensions(), req.headers())
+        .event(event_type)
+        .actor(actor_id)
+        .target(format!("{} {}", req.method(), path))
+        .detail(message)
+}
@@ -0,0 +1,73 @@
+use std::{net::SocketAddr, sync::Arc, time::Instant};
+use axum::{
+    body::{to_bytes, Body}, extract::{ConnectInfo, State}, http::{header, Request, StatusCode}, middleware::Next, response::{IntoResponse, Response}, Json
+};
+use serde::Deserialize;
+
+use crate::{
+    middlewares::auth_guard::ErrorResponse,
+    services::login_throttle::ThrottleKey,
+    AppState
+};
+
+const MAX_LOGIN_BODY_BYTES: usize = 64 * 1024;
+
+#[derive(Deserialize)]
+struct LoginUsername {
+    username: String,
+}
+
+/// Throttles repeated failed logins per username and per client IP. Every
+/// attempt is counted as a failure before the wrapped handler runs; its status
+/// then decides the outcome: 401 keeps the failure, 200 gives it back and
+/// clears the username's record, and anything else just gives it back.
+pub async fn throttle_login(
+    State(app_state): State<Arc<AppState>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    req: Request<Body>,
+    next: Next,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+
+    let (parts, body) = req.into_parts();
+    let bytes = to_bytes(body, MAX_LOGIN_BODY_BYTES).await.map_err(|_| {
+        let json_error = ErrorResponse {
+            success: false,
+            message: "Request body is too large".to_string(),
+        };
+        (StatusCode::PAYLOAD_TOO_LARGE, Json(json_error))
+    })?;
+
+    let mut keys = vec![ThrottleKey::Ip(client.ip())];
+    let username_key = serde_json::from_slice::<LoginUsername>(&bytes)
+        .ok()
+        .map(|body| ThrottleKey::Username(body.username.to_ascii_lowercase()));
+    keys.extend(username_key.clone());
+
+    if let Err(wait) = app_state.login_throttle.reserve(&keys, Instant::now()) {
+        let retry_after = wait.as_secs().max(1);
+        let json_error = ErrorResponse {
+            success: false,
+            message: format!("Too many failed login attempts, try again in {} seconds", retry_after),
+        };
+        return Ok((
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, retry_after.to_string())],
+            Json(json_error),
+        ).into_response());
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(bytes))).await;
+
+    match response.status() {
+        StatusCode::UNAUTHORIZED => {}
+        StatusCode::OK => {
+            app_state.login_throttle.release(&keys);
+            if let Some(key) = &username_key {
+                app_state.login_throttle.clear(key);
+            }
+        }
+        _ => app_state.login_throttle.release(&keys),
+    }
+
+    Ok(response)
+}
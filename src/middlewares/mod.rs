@@ -1 +1,2 @@
-pub mod auth_guard;
\ No newline at end of file
+pub mod auth_guard;
+pub mod login_guard;
\ No newline at end of file
@@ -0,0 +1,22 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::auth_model::Role;
+
+#[derive(Debug, Serialize)]
+pub struct AccountModel {
+    pub id: Uuid,
+    pub full_name: String,
+    pub username: String,
+    pub role: Role,
+    pub is_active: bool,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateAccountModel {
+    pub full_name: Option<String>,
+    pub role: Option<Role>,
+}
@@ -0,0 +1,29 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Scopes an API key can be granted. Each resource has a read scope for GET
+/// requests and a write scope for everything else.
+pub const API_KEY_SCOPES: [&str; 4] = [
+    "catalog:read",
+    "catalog:write",
+    "transactions:read",
+    "transactions:write",
+];
+
+#[derive(Debug, Serialize)]
+pub struct ApiKeyModel {
+    pub key_id: String,
+    pub key_name: String,
+    pub scopes: Vec<String>,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiKeyModel {
+    pub key_name: String,
+    pub scopes: Vec<String>,
+}
@@ -0,0 +1,37 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "audit_outcome", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditLogModel {
+    pub id: i64,
+    pub event_type: String,
+    pub outcome: AuditOutcome,
+    pub actor_id: Option<Uuid>,
+    pub username: Option<String>,
+    pub target: Option<String>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub detail: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct AuditFilterModel {
+    pub offset: Option<i64>,
+    pub limit: Option<i64>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub actor_id: Option<Uuid>,
+    pub username: Option<String>,
+    pub event_type: Option<String>,
+    pub outcome: Option<AuditOutcome>,
+}
@@ -2,9 +2,48 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Account roles, ordered from least to most privileged so that
+/// `role >= Role::Manager` reads as "manager or above".
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "account_role", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    #[default]
+    Cashier,
+    Manager,
+    Owner,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Cashier => "cashier",
+            Role::Manager => "manager",
+            Role::Owner => "owner",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TokenClaims {
     pub sub: String,
+    pub role: Role,
+    pub sid: String,
+    /// The store this session acts in.
+    pub store_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub register_id: Option<String>,
+    pub iat: usize,
+    pub exp: usize,
+}
+
+/// Claims of the short-lived token that links the password step of a login
+/// to its TOTP step. Its audience keeps it from being used as an access token.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MfaClaims {
+    pub sub: String,
+    pub aud: String,
+    pub store_id: String,
     pub iat: usize,
     pub exp: usize,
 }
@@ -13,6 +52,97 @@ pub struct TokenClaims {
 pub struct LoginModel {
     pub username: String,
     pub password: String,
+    pub store_id: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TotpLoginModel {
+    pub username: String,
+    pub mfa_token: String,
+    pub code: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TotpEnrollLoginModel {
+    pub username: String,
+    pub mfa_token: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EnrollTotpModel {
+    pub current_password: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ConfirmTotpModel {
+    pub current_password: String,
+    pub code: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DisableTotpModel {
+    pub current_password: String,
+    pub code: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RolePolicyModel {
+    pub role: Role,
+    pub require_totp: bool,
+    pub updated_by: Option<Uuid>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct UpdateRolePolicyModel {
+    pub require_totp: bool,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PinLoginModel {
+    pub register_id: String,
+    pub register_key: String,
+    pub username: String,
+    pub pin: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SetPinModel {
+    pub current_password: String,
+    pub pin: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RefreshModel {
+    pub refresh_token: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ChangePasswordModel {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ResetPasswordModel {
+    pub reset_token: String,
+    pub new_password: String,
+}
+
+/// Signup body. `role` is only honoured when a manager creates the account;
+/// invited accounts take the role from their invite.
+#[derive(Deserialize, Debug)]
+pub struct SignupInputModel {
+    pub full_name: String,
+    pub username: String,
+    pub password: String,
+    pub role: Option<Role>,
+    pub invite_code: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CreateInviteModel {
+    pub role: Option<Role>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -21,6 +151,9 @@ pub struct  SignupModel {
     pub full_name: String,
     pub username: String,
     pub password: String,
+    #[serde(default)]
+    pub role: Role,
+    pub is_active: Option<bool>,
     pub created_at: Option<DateTime<Utc>>,
     pub updated_at: Option<DateTime<Utc>>,
-}
\ No newline at end of file
+}
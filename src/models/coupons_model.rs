@@ -0,0 +1,51 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "discount_type", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum DiscountType {
+    Percent,
+    Fixed,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CouponModel {
+    pub coupon_id: String,
+    pub code: String,
+    pub discount_type: DiscountType,
+    pub discount_value: Decimal,
+    pub valid_from: Option<DateTime<Utc>>,
+    pub valid_until: Option<DateTime<Utc>>,
+    pub max_uses: Option<i32>,
+    pub max_uses_per_customer: Option<i32>,
+    pub min_basket: Option<Decimal>,
+    pub is_active: bool,
+    /// Redemptions on sales that were not voided.
+    pub times_used: i32,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateCouponModel {
+    pub code: String,
+    pub discount_type: DiscountType,
+    pub discount_value: Decimal,
+    pub valid_from: Option<DateTime<Utc>>,
+    pub valid_until: Option<DateTime<Utc>>,
+    pub max_uses: Option<i32>,
+    pub max_uses_per_customer: Option<i32>,
+    pub min_basket: Option<Decimal>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateCouponModel {
+    pub valid_from: Option<DateTime<Utc>>,
+    pub valid_until: Option<DateTime<Utc>>,
+    pub max_uses: Option<i32>,
+    pub max_uses_per_customer: Option<i32>,
+    pub min_basket: Option<Decimal>,
+    pub is_active: Option<bool>,
+}
@@ -0,0 +1,28 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize)]
+pub struct InvoiceModel {
+    pub invoice_number: i64,
+    pub transaction_id: String,
+    /// Our legal details as they were when the invoice was issued.
+    pub seller_name: String,
+    pub seller_address: Option<String>,
+    pub seller_tax_id: Option<String>,
+    pub customer_name: String,
+    pub company_name: Option<String>,
+    pub billing_address: String,
+    pub customer_tax_id: Option<String>,
+    pub customer_email: Option<String>,
+    pub issued_at: DateTime<Utc>,
+}
+
+/// The customer's billing details. `billing_address` may span several lines.
+#[derive(Debug, Deserialize)]
+pub struct CreateInvoiceModel {
+    pub customer_name: String,
+    pub company_name: Option<String>,
+    pub billing_address: String,
+    pub customer_tax_id: Option<String>,
+    pub customer_email: Option<String>,
+}
@@ -2,4 +2,15 @@ pub mod products_model;
 pub mod categories_model;
 pub mod transactions_model;
 pub mod filter_model;
-pub mod auth_model;
\ No newline at end of file
+pub mod auth_model;
+pub mod accounts_model;
+pub mod registers_model;
+pub mod api_keys_model;
+pub mod audit_model;
+pub mod stores_model;
+pub mod payments_model;
+pub mod taxes_model;
+pub mod promotions_model;
+pub mod coupons_model;
+pub mod receipts_model;
+pub mod invoices_model;
\ No newline at end of file
@@ -0,0 +1,22 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "tender_type", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum TenderType {
+    Cash,
+    Card,
+    Mobile,
+    Voucher,
+    Other,
+}
+
+/// One tender towards a sale. Only cash may exceed what is left to pay; the
+/// difference is handed back as change.
+#[derive(Debug, Deserialize)]
+pub struct PaymentInput {
+    pub tender_type: TenderType,
+    pub amount: Decimal,
+    pub reference: Option<String>,
+}
@@ -10,7 +10,9 @@ pub struct GetProductModel {
     pub stock: Option<i32>,
     pub sku: Option<String>,
     pub category_name: Option<String>,
+    pub tax_class_id: Option<String>,
     pub product_image: Option<String>,
+    pub is_active: Option<bool>,
     pub created_at: Option<DateTime<Utc>>,
     pub updated_at: Option<DateTime<Utc>>,
 }
@@ -23,7 +25,9 @@ pub struct PostProductModel {
     pub stock: Option<i32>,
     pub sku: Option<String>,
     pub category_id: Option<String>,
+    pub tax_class_id: Option<String>,
     pub product_image: Option<String>,
+    pub is_active: Option<bool>,
     pub created_at: Option<DateTime<Utc>>,
     pub updated_at: Option<DateTime<Utc>>,
 }
\ No newline at end of file
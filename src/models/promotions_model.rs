@@ -0,0 +1,91 @@
+use chrono::{DateTime, NaiveTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+/// How a promotion picks the units it discounts. `BuyXGetY` and
+/// `QuantityBreak` apply to one product, `CategoryPercent` to every product of
+/// a category.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "promotion_type", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum PromotionType {
+    BuyXGetY,
+    QuantityBreak,
+    CategoryPercent,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PromotionModel {
+    pub promotion_id: String,
+    pub promotion_name: String,
+    pub promotion_type: PromotionType,
+    pub product_id: Option<String>,
+    pub category_id: Option<String>,
+    pub buy_quantity: Option<i32>,
+    pub get_quantity: Option<i32>,
+    pub min_quantity: Option<i32>,
+    pub discount_percent: Decimal,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub daily_start: Option<NaiveTime>,
+    pub daily_end: Option<NaiveTime>,
+    pub is_active: bool,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreatePromotionModel {
+    pub promotion_name: String,
+    pub promotion_type: PromotionType,
+    pub product_id: Option<String>,
+    pub category_id: Option<String>,
+    pub buy_quantity: Option<i32>,
+    pub get_quantity: Option<i32>,
+    pub min_quantity: Option<i32>,
+    /// Defaults to 100 for buy X get Y, making the extra units free.
+    pub discount_percent: Option<Decimal>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub daily_start: Option<NaiveTime>,
+    pub daily_end: Option<NaiveTime>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdatePromotionModel {
+    pub promotion_name: Option<String>,
+    pub discount_percent: Option<Decimal>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub is_active: Option<bool>,
+}
+
+/// A discount the cashier gives by hand, on one line or on the whole order.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(tag = "discount_type", content = "value", rename_all = "snake_case")]
+pub enum DiscountInput {
+    Percent(Decimal),
+    Fixed(Decimal),
+}
+
+/// Where a discount on a sale came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "discount_source", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum DiscountSource {
+    Promotion,
+    Line,
+    Order,
+    Coupon,
+}
+
+/// A discount as recorded on a sale.
+#[derive(Debug, Clone, Serialize)]
+pub struct AppliedDiscount {
+    pub line_number: Option<i32>,
+    pub discount_source: DiscountSource,
+    pub promotion_id: Option<String>,
+    pub coupon_id: Option<String>,
+    pub description: String,
+    pub amount: Decimal,
+}
@@ -0,0 +1,27 @@
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReceiptFormat {
+    #[default]
+    Text,
+    Escpos,
+    Html,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReceiptOptionsModel {
+    pub format: Option<ReceiptFormat>,
+    /// Characters per line of the printer: 42 or 48.
+    pub width: Option<usize>,
+}
+
+/// The store details printed at the top and bottom of a receipt.
+#[derive(Debug)]
+pub struct ReceiptStoreModel {
+    pub store_name: String,
+    pub address: Option<String>,
+    pub tax_id: Option<String>,
+    pub receipt_header: Option<String>,
+    pub receipt_footer: Option<String>,
+}
@@ -0,0 +1,13 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RegisterModel {
+    pub register_id: Option<String>,
+    /// Numbered per store and printed in receipt numbers as `R02`.
+    pub register_number: Option<i32>,
+    pub register_name: Option<String>,
+    pub is_active: Option<bool>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,66 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// The store a request acts in. Set by the auth middlewares from the token's
+/// claims or from the API key, and used to scope every catalog and sales query.
+#[derive(Debug, Clone)]
+pub struct ActiveStore(pub String);
+
+/// What recording a sale does when a line needs more stock than is left.
+/// `AllowNegative` lets stock go below zero; `Warn` does the same but reports
+/// the shortfall in the response.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "stock_policy", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum StockPolicy {
+    #[default]
+    Reject,
+    AllowNegative,
+    Warn,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StoreModel {
+    pub store_id: String,
+    /// Printed in receipt numbers as `S01`.
+    pub store_number: i32,
+    pub store_name: String,
+    pub is_active: bool,
+    pub stock_policy: StockPolicy,
+    pub prices_include_tax: bool,
+    pub address: Option<String>,
+    pub tax_id: Option<String>,
+    pub receipt_header: Option<String>,
+    pub receipt_footer: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateStoreModel {
+    pub store_name: String,
+    pub stock_policy: Option<StockPolicy>,
+    pub prices_include_tax: Option<bool>,
+    pub address: Option<String>,
+    pub tax_id: Option<String>,
+    pub receipt_header: Option<String>,
+    pub receipt_footer: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateStoreModel {
+    pub store_name: Option<String>,
+    pub is_active: Option<bool>,
+    pub stock_policy: Option<StockPolicy>,
+    pub prices_include_tax: Option<bool>,
+    pub address: Option<String>,
+    pub tax_id: Option<String>,
+    pub receipt_header: Option<String>,
+    pub receipt_footer: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StoreMemberModel {
+    pub account_id: Uuid,
+}
@@ -0,0 +1,33 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Serialize)]
+pub struct TaxClassModel {
+    pub tax_class_id: String,
+    pub class_name: String,
+    /// The class's rates, embedded as a JSON list.
+    pub rates: Value,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TaxClassInputModel {
+    pub class_name: String,
+}
+
+/// A percentage charged on the taxable amount, e.g. `19` for 19%.
+#[derive(Debug, Clone, Serialize)]
+pub struct TaxRateModel {
+    pub tax_rate_id: String,
+    pub rate_name: String,
+    pub rate: Decimal,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateTaxRateModel {
+    pub rate_name: String,
+    pub rate: Decimal,
+}
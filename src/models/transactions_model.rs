@@ -2,25 +2,133 @@ use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use uuid::Uuid;
+
+use crate::models::{payments_model::PaymentInput, promotions_model::DiscountInput};
+
+/// Refunds are recorded as transactions of their own, with negative
+/// quantities and totals, pointing back at the sale they reverse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "transaction_type", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionType {
+    Sale,
+    Refund,
+}
 
 #[derive(Debug, Serialize)]
 pub struct TransactionModel {
     pub transaction_id: Option<String>,
+    /// Numbered per register without gaps, e.g. `S01-R02-000123`.
+    pub receipt_number: String,
+    pub transaction_type: TransactionType,
+    pub original_transaction_id: Option<String>,
     pub transaction_date: Option<DateTime<Utc>>,
+    /// The account that recorded the transaction.
+    pub cashier_id: Option<Uuid>,
+    pub cashier_name: Option<String>,
+    /// The register it was recorded at, for register sessions.
+    pub register_id: Option<String>,
+    pub register_name: Option<String>,
     pub total_price: Option<Decimal>,
+    pub discount_total: Decimal,
+    pub tax_total: Decimal,
+    pub prices_include_tax: bool,
+    pub change_due: Decimal,
     pub item_count: Option<i32>,
+    pub reason: Option<String>,
+    pub voided_at: Option<DateTime<Utc>>,
+    /// The lines from `transaction_items`, embedded as a JSON list.
     pub transaction_items: Value,
+    /// The promotions and manual discounts given, embedded as a JSON list.
+    pub discounts: Value,
+    /// Tax per rate over all lines, embedded as a JSON list.
+    pub tax_breakdown: Value,
+    /// The tenders from `payments`, embedded as a JSON list.
+    pub payments: Value,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TransactionFilterModel {
+    pub offset: Option<i64>,
+    pub limit: Option<i64>,
+    /// Matches any part of the receipt number, e.g. `000123` or `S01-R02`.
+    pub receipt_number: Option<String>,
+    pub cashier_id: Option<Uuid>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct TransactionInputModel {
-    pub transaction_items: Vec<TransactionItem>,
+    pub transaction_items: Vec<TransactionItemInput>,
+    pub discount: Option<DiscountInput>,
+    pub coupon_code: Option<String>,
+    /// Identifies the customer for per-customer coupon limits, e.g. a loyalty
+    /// card number.
+    pub customer_ref: Option<String>,
+    #[serde(default)]
+    pub payments: Vec<PaymentInput>,
 }
 
+/// A line as sent by the client. Everything else about the product is
+/// looked up on the server.
+#[derive(Debug, Deserialize)]
+pub struct TransactionItemInput {
+    pub product_id: String,
+    pub quantity: u32,
+    pub discount: Option<DiscountInput>,
+}
+
+/// A line as recorded on the transaction, priced from `products` at the time
+/// of sale. Refund lines have a negative quantity and reference the sale line
+/// they return.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TransactionItem {
-    pub product_name: String,
-    pub product_category: String,
+    pub product_id: Option<String>,
+    pub product_name: Option<String>,
+    pub product_category: Option<String>,
+    pub sku: Option<String>,
+    pub quantity: i32,
+    pub price: Decimal,
+    pub line_discount: Decimal,
+    pub line_tax: Decimal,
+    pub line_total: Decimal,
+    pub original_line_number: Option<i32>,
+    pub taxes: Vec<LineTax>,
+}
+
+/// The tax one rate adds to one line.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LineTax {
+    pub tax_rate_id: Option<String>,
+    pub rate_name: String,
+    pub rate: Decimal,
+    pub taxable_amount: Decimal,
+    pub tax_amount: Decimal,
+}
+
+/// Lines to return. Without `items` everything not yet refunded is returned.
+#[derive(Debug, Deserialize)]
+pub struct RefundInputModel {
+    pub items: Option<Vec<RefundItemInput>>,
+    pub reason: Option<String>,
+    pub restock: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefundItemInput {
+    pub line_number: i32,
     pub quantity: u32,
-    pub price: f64,
-}
\ No newline at end of file
+}
+
+/// A product's stock after a sale was recorded.
+#[derive(Debug, Serialize)]
+pub struct StockLevelModel {
+    pub product_id: String,
+    pub stock: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TransactionStoreModel {
+    pub store_id: String,
+    pub store_name: String,
+}
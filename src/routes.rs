@@ -1,24 +1,56 @@
 use std::sync::Arc;
-use axum::{http::{header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE}, Method, StatusCode}, middleware, response::IntoResponse, routing::{get, patch, post}, Router};
+use axum::{extract::State, handler::Handler, http::{header::{HeaderName, ACCEPT, AUTHORIZATION, CONTENT_TYPE}, Method, StatusCode}, middleware, response::IntoResponse, routing::{delete, get, patch, post, put}, Json, Router};
 use tower_http::{cors::{Any, CorsLayer}, trace::TraceLayer};
 
 use crate::{
     handlers::{
-        auth::{login, signup},
+        account::{
+            create_invite, create_password_reset, deactivate_account, get_account, get_all_accounts,
+            reactivate_account, revoke_account_sessions, unlock_account, update_account},
+        api_key::{create_api_key, get_all_api_keys, revoke_api_key},
+        audit::get_audit_log,
+        auth::{
+            change_password, login, login_totp, login_totp_enroll, logout, pin_login, refresh, reset_password,
+            set_pin, signup},
         category::{create_category, delete_category, get_all_categories, update_category},
+        coupon::{create_coupon, delete_coupon, get_all_coupons, update_coupon, validate_coupon},
+        invoice::{create_invoice, get_invoice},
         product::{create_product, delete_product, get_all_products, get_product, update_product},
-        transaction::{create_transaction, get_all_transactions}
+        promotion::{create_promotion, delete_promotion, get_all_promotions, update_promotion},
+        register::{create_register, get_all_registers, revoke_register},
+        store::{add_store_member, create_store, get_all_stores, remove_store_member, switch_store, update_store},
+        tax::{
+            create_tax_class, create_tax_rate, delete_tax_class, delete_tax_rate, get_all_tax_classes,
+            update_tax_class},
+        totp::{confirm_totp, disable_totp, enroll_totp, get_role_policies, update_role_policy},
+        transaction::{
+            create_transaction, get_all_transactions, get_receipt, get_transaction, refund_transaction,
+            void_transaction}
     },
-    middlewares::auth_guard::auth,
+    middlewares::{
+        auth_guard::{auth, auth_or_api_key, require_role, API_KEY_HEADER},
+        login_guard::throttle_login},
+    models::auth_model::Role,
     AppState
 };
 
 pub fn app_router(app_state: Arc<AppState>) -> Router {
     Router::new()
         .route("/", get(root))
+        .route("/.well-known/jwks.json", get(jwks).with_state(app_state.clone()))
             .nest("/api", auth_route(app_state.clone()))
+            .nest("/api/account", account_route(app_state.clone()))
+            .nest("/api/audit", audit_route(app_state.clone()))
+            .nest("/api/totp", totp_route(app_state.clone()))
+            .nest("/api/role-policy", role_policy_route(app_state.clone()))
+            .nest("/api/store", store_route(app_state.clone()))
+            .nest("/api/api-key", api_key_route(app_state.clone()))
+            .nest("/api/register", register_route(app_state.clone()))
             .nest("/api/product", product_route(app_state.clone()))
             .nest("/api/category", category_route(app_state.clone()))
+            .nest("/api/tax-class", tax_class_route(app_state.clone()))
+            .nest("/api/promotion", promotion_route(app_state.clone()))
+            .nest("/api/coupon", coupon_route(app_state.clone()))
             .nest("/api/transaction", transaction_route(app_state.clone()))
         .layer(TraceLayer::new_for_http())
         .layer(CorsLayer::new()
@@ -26,6 +58,7 @@ pub fn app_router(app_state: Arc<AppState>) -> Router {
             .allow_methods([
                 Method::GET,
                 Method::POST,
+                Method::PUT,
                 Method::PATCH,
                 Method::DELETE,
                 ])
@@ -33,6 +66,7 @@ pub fn app_router(app_state: Arc<AppState>) -> Router {
                 AUTHORIZATION,
                 CONTENT_TYPE,
                 ACCEPT,
+                HeaderName::from_static(API_KEY_HEADER),
                 ]))
         .fallback(handler_404)
 }
@@ -41,6 +75,10 @@ async fn root() -> &'static str {
     "Server is running"
 }
 
+async fn jwks(State(app_state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(app_state.jwt.jwks())
+}
+
 async fn handler_404() -> impl IntoResponse {
     (
         StatusCode::NOT_FOUND,
@@ -53,28 +91,174 @@ async fn handle_405() -> impl IntoResponse {
 
 pub fn auth_route(app_state: Arc<AppState>) -> Router {
     Router::new()
-        .route("/login", post(login))
+        .route("/login", post(login
+            .layer(middleware::from_fn_with_state(app_state.clone(), throttle_login))))
         .route("/signup", post(signup))
+        .route("/refresh", post(refresh))
+        .route("/logout", post(logout
+            .layer(middleware::from_fn_with_state(app_state.clone(), auth))))
+        .route("/password/change", post(change_password
+            .layer(middleware::from_fn_with_state(app_state.clone(), auth))))
+        .route("/password/reset", post(reset_password))
+        .route("/pin", put(set_pin
+            .layer(middleware::from_fn_with_state(app_state.clone(), auth))))
+        .route("/login/totp", post(login_totp
+            .layer(middleware::from_fn_with_state(app_state.clone(), throttle_login))))
+        .route("/login/totp/enroll", post(login_totp_enroll
+            .layer(middleware::from_fn_with_state(app_state.clone(), throttle_login))))
+        .route("/pin-login", post(pin_login
+            .layer(middleware::from_fn_with_state(app_state.clone(), throttle_login))))
+        .with_state(app_state)
+        .method_not_allowed_fallback(handle_405)
+}
+
+pub fn account_route(app_state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/", get(get_all_accounts))
+        .route("/invites", post(create_invite))
+        .route("/{account_id}", get(get_account).patch(update_account))
+        .route("/{account_id}/deactivate", post(deactivate_account))
+        .route("/{account_id}/reactivate", post(reactivate_account))
+        .route("/{account_id}/unlock", post(unlock_account))
+        .route("/{account_id}/sessions/revoke", post(revoke_account_sessions))
+        .route("/{account_id}/password-reset", post(create_password_reset))
+        .route_layer(middleware::from_fn_with_state((app_state.clone(), Role::Manager), require_role))
+        .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        .with_state(app_state)
+        .method_not_allowed_fallback(handle_405)
+}
+
+pub fn audit_route(app_state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/", get(get_audit_log))
+        .route_layer(middleware::from_fn_with_state((app_state.clone(), Role::Manager), require_role))
+        .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        .with_state(app_state)
+        .method_not_allowed_fallback(handle_405)
+}
+
+pub fn totp_route(app_state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/enroll", post(enroll_totp))
+        .route("/confirm", post(confirm_totp))
+        .route("/disable", post(disable_totp))
+        .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        .with_state(app_state)
+        .method_not_allowed_fallback(handle_405)
+}
+
+pub fn role_policy_route(app_state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/", get(get_role_policies))
+        .route("/{role}", put(update_role_policy))
+        .route_layer(middleware::from_fn_with_state((app_state.clone(), Role::Owner), require_role))
+        .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        .with_state(app_state)
+        .method_not_allowed_fallback(handle_405)
+}
+
+pub fn store_route(app_state: Arc<AppState>) -> Router {
+    let owner_only = || middleware::from_fn_with_state((app_state.clone(), Role::Owner), require_role);
+
+    Router::new()
+        .route("/", get(get_all_stores)
+            .post(create_store.layer(owner_only())))
+        .route("/{store_id}", patch(update_store.layer(owner_only())))
+        .route("/{store_id}/members", post(add_store_member.layer(owner_only())))
+        .route("/{store_id}/members/{account_id}", delete(remove_store_member.layer(owner_only())))
+        .route("/{store_id}/switch", post(switch_store))
+        .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        .with_state(app_state)
+        .method_not_allowed_fallback(handle_405)
+}
+
+pub fn api_key_route(app_state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/", get(get_all_api_keys).post(create_api_key))
+        .route("/{key_id}", delete(revoke_api_key))
+        .route_layer(middleware::from_fn_with_state((app_state.clone(), Role::Manager), require_role))
+        .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        .with_state(app_state)
+        .method_not_allowed_fallback(handle_405)
+}
+
+pub fn register_route(app_state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/", get(get_all_registers).post(create_register))
+        .route("/{register_id}", delete(revoke_register))
+        .route_layer(middleware::from_fn_with_state((app_state.clone(), Role::Manager), require_role))
+        .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
         .with_state(app_state)
         .method_not_allowed_fallback(handle_405)
 }
 
 pub fn product_route(app_state: Arc<AppState>) -> Router {
     Router::new()
-        .route("/", get(get_all_products).post(create_product))
+        .route("/", get(get_all_products)
+            .post(create_product.layer(middleware::from_fn_with_state((app_state.clone(), Role::Manager), require_role))))
         .route("/{product_id}", get( get_product)
             .patch(update_product)
-            .delete(delete_product))
-        .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+            .delete(delete_product.layer(middleware::from_fn_with_state((app_state.clone(), Role::Manager), require_role))))
+        .route_layer(middleware::from_fn_with_state((app_state.clone(), "catalog"), auth_or_api_key))
         .with_state(app_state)
         .method_not_allowed_fallback(handle_405)
 }
 
 pub fn category_route(app_state: Arc<AppState>) -> Router {
     Router::new()
-        .route("/", get(get_all_categories).post(create_category))
-        .route("/{category_id}", patch(update_category).delete(delete_category))
-        .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        .route("/", get(get_all_categories)
+            .post(create_category.layer(middleware::from_fn_with_state((app_state.clone(), Role::Manager), require_role))))
+        .route("/{category_id}", patch(update_category)
+            .delete(delete_category)
+            .route_layer(middleware::from_fn_with_state((app_state.clone(), Role::Manager), require_role)))
+        .route_layer(middleware::from_fn_with_state((app_state.clone(), "catalog"), auth_or_api_key))
+        .with_state(app_state)
+        .method_not_allowed_fallback(handle_405)
+}
+
+pub fn tax_class_route(app_state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/", get(get_all_tax_classes)
+            .post(create_tax_class.layer(middleware::from_fn_with_state((app_state.clone(), Role::Manager), require_role))))
+        .route("/{tax_class_id}", patch(update_tax_class)
+            .delete(delete_tax_class)
+            .route_layer(middleware::from_fn_with_state((app_state.clone(), Role::Manager), require_role)))
+        .route("/{tax_class_id}/rates", post(create_tax_rate)
+            .route_layer(middleware::from_fn_with_state((app_state.clone(), Role::Manager), require_role)))
+        .route("/{tax_class_id}/rates/{tax_rate_id}", delete(delete_tax_rate)
+            .route_layer(middleware::from_fn_with_state((app_state.clone(), Role::Manager), require_role)))
+        .route_layer(middleware::from_fn_with_state((app_state.clone(), "catalog"), auth_or_api_key))
+        .with_state(app_state)
+        .method_not_allowed_fallback(handle_405)
+}
+
+pub fn promotion_route(app_state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/", get(get_all_promotions)
+            .post(create_promotion.layer(middleware::from_fn_with_state((app_state.clone(), Role::Manager), require_role))))
+        .route("/{promotion_id}", patch(update_promotion)
+            .delete(delete_promotion)
+            .route_layer(middleware::from_fn_with_state((app_state.clone(), Role::Manager), require_role)))
+        .route_layer(middleware::from_fn_with_state((app_state.clone(), "catalog"), auth_or_api_key))
+        .with_state(app_state)
+        .method_not_allowed_fallback(handle_405)
+}
+
+/// Validating a code is part of ringing up a sale, so it takes the
+/// `transactions` scope; managing coupons takes `catalog`.
+pub fn coupon_route(app_state: Arc<AppState>) -> Router {
+    let validate = Router::new()
+        .route("/validate", post(validate_coupon))
+        .route_layer(middleware::from_fn_with_state((app_state.clone(), "transactions"), auth_or_api_key));
+
+    Router::new()
+        .route("/", get(get_all_coupons)
+            .post(create_coupon.layer(middleware::from_fn_with_state((app_state.clone(), Role::Manager), require_role))))
+        .route("/{coupon_id}", patch(update_coupon)
+            .delete(delete_coupon)
+            .route_layer(middleware::from_fn_with_state((app_state.clone(), Role::Manager), require_role)))
+        .route_layer(middleware::from_fn_with_state((app_state.clone(), "catalog"), auth_or_api_key))
+        .merge(validate)
         .with_state(app_state)
         .method_not_allowed_fallback(handle_405)
 }
@@ -82,7 +266,13 @@ pub fn category_route(app_state: Arc<AppState>) -> Router {
 pub fn transaction_route(app_state: Arc<AppState>) -> Router {
     Router::new()
         .route("/", get(get_all_transactions).post(create_transaction))
-        .route_layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        .route("/{transaction_id}", get(get_transaction))
+        .route("/{transaction_id}/receipt", get(get_receipt))
+        .route("/{transaction_id}/invoice", get(get_invoice).post(create_invoice))
+        .route("/{transaction_id}/refund", post(refund_transaction
+            .layer(middleware::from_fn_with_state((app_state.clone(), Role::Manager), require_role))))
+        .route("/{transaction_id}/void", post(void_transaction))
+        .route_layer(middleware::from_fn_with_state((app_state.clone(), "transactions"), auth_or_api_key))
         .with_state(app_state)
         .method_not_allowed_fallback(handle_405)
 }
\ No newline at end of file
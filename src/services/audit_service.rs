@@ -0,0 +1,132 @@
+use std::{convert::Infallible, net::SocketAddr};
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header, request::Parts, Extensions, HeaderMap, StatusCode},
+    Json
+};
+use chrono::Utc;
+use serde_json::Value;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::models::audit_model::AuditOutcome;
+
+/// Where a request came from, as recorded in the audit log.
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl ClientInfo {
+    pub fn from_parts(extensions: &Extensions, headers: &HeaderMap) -> Self {
+        Self {
+            ip_address: extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip().to_string()),
+            user_agent: headers
+                .get(header::USER_AGENT)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned),
+        }
+    }
+
+    pub fn event(&self, event_type: &'static str) -> AuditEvent {
+        AuditEvent {
+            event_type,
+            client: self.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for ClientInfo {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self::from_parts(&parts.extensions, &parts.headers))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct AuditEvent {
+    event_type: &'static str,
+    client: ClientInfo,
+    actor_id: Option<Uuid>,
+    username: Option<String>,
+    target: Option<String>,
+    detail: Option<String>,
+}
+
+impl AuditEvent {
+    pub fn actor(mut self, actor_id: Option<Uuid>) -> Self {
+        self.actor_id = actor_id;
+        self
+    }
+
+    /// Username the request was made for. When no actor is set, the event is
+    /// linked to the account with this username, if there is one.
+    pub fn username(mut self, username: &str) -> Self {
+        self.username = Some(username.to_ascii_lowercase());
+        self
+    }
+
+    pub fn target(mut self, target: impl ToString) -> Self {
+        self.target = Some(target.to_string());
+        self
+    }
+
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+}
+
+/// Writes an event to the audit log. A failed write is logged but never
+/// fails the request being audited.
+pub async fn record(db: &PgPool, event: AuditEvent, outcome: AuditOutcome) {
+    let result = sqlx::query!(
+        r#"
+            INSERT INTO audit_log (
+                event_type, outcome, actor_id, username, target, ip_address, user_agent, detail, created_at
+            )
+            VALUES (
+                $1, $2, COALESCE($3, (SELECT id FROM accounts WHERE username = $4)), $4, $5, $6, $7, $8, $9
+            )
+        "#,
+        event.event_type,
+        outcome as AuditOutcome,
+        event.actor_id,
+        event.username,
+        event.target,
+        event.client.ip_address,
+        event.client.user_agent,
+        event.detail,
+        Utc::now(),
+    )
+    .execute(db)
+    .await;
+
+    if let Err(e) = result {
+        tracing::error!("Failed to write {} audit event: {}", event.event_type, e);
+    }
+}
+
+/// Records the outcome of a handler result. Failures keep the error message
+/// as the event detail.
+pub async fn record_result<T>(
+    db: &PgPool,
+    event: AuditEvent,
+    result: &Result<T, (StatusCode, Json<Value>)>,
+) {
+    match result {
+        Ok(_) => record(db, event, AuditOutcome::Success).await,
+        Err((_, Json(error))) => {
+            let event = match error["message"].as_str() {
+                Some(message) => event.detail(message),
+                None => event,
+            };
+            record(db, event, AuditOutcome::Failure).await
+        }
+    }
+}
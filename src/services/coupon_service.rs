@@ -0,0 +1,255 @@
+use axum::{http::StatusCode, Json};
+use chrono::Utc;
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+use sqlx::PgConnection;
+
+use crate::{
+    models::{
+        coupons_model::{CouponModel, DiscountType},
+        stores_model::ActiveStore},
+    services::tax_service::round_money,
+};
+
+/// Looks up `code` in the store, checks that it can be redeemed now by
+/// `customer_ref` and takes one of its uses. The coupon row stays locked
+/// until the surrounding transaction ends, so concurrent sales using the same
+/// code are redeemed one after the other and cannot exceed its limits; a
+/// rollback gives the use back.
+pub async fn claim_coupon(
+    conn: &mut PgConnection,
+    store: &ActiveStore,
+    code: &str,
+    customer_ref: Option<&str>,
+) -> Result<CouponModel, (StatusCode, Json<Value>)> {
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    };
+    let rejected = |status: StatusCode, message: &str| {
+        (
+            status,
+            Json(json!({
+                "success": false,
+                "message": message,
+            })),
+        )
+    };
+
+    let coupon = sqlx::query_as!(
+        CouponModel,
+        r#"
+            SELECT
+                coupon_id, code, discount_type AS "discount_type: DiscountType", discount_value, valid_from,
+                valid_until, max_uses, max_uses_per_customer, min_basket, is_active, times_used,
+                created_at, updated_at
+            FROM coupons
+            WHERE store_id = $1 AND code = UPPER($2)
+            FOR UPDATE
+        "#,
+        store.0,
+        code.trim(),
+    )
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(db_error)?
+    .ok_or_else(|| rejected(StatusCode::NOT_FOUND, "Coupon not found"))?;
+
+    let now = Utc::now();
+    if !coupon.is_active
+        || coupon.valid_from.is_some_and(|valid_from| valid_from > now)
+        || coupon.valid_until.is_some_and(|valid_until| valid_until <= now)
+    {
+        return Err(rejected(StatusCode::BAD_REQUEST, "Coupon is not valid at this time"));
+    }
+
+    if let Some(max_uses_per_customer) = coupon.max_uses_per_customer {
+        let customer_ref = customer_ref
+            .map(str::trim)
+            .filter(|customer_ref| !customer_ref.is_empty())
+            .ok_or_else(|| rejected(StatusCode::BAD_REQUEST, "This coupon needs a customer_ref"))?;
+
+        let customer_uses = sqlx::query_scalar!(
+            r#"
+                SELECT COUNT(*) AS "count!"
+                FROM coupon_redemptions
+                JOIN transactions ON transactions.transaction_id = coupon_redemptions.transaction_id
+                WHERE coupon_redemptions.coupon_id = $1
+                    AND coupon_redemptions.customer_ref = $2
+                    AND transactions.voided_at IS NULL
+            "#,
+            coupon.coupon_id,
+            customer_ref,
+        )
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(db_error)?;
+
+        if customer_uses >= i64::from(max_uses_per_customer) {
+            return Err(rejected(StatusCode::CONFLICT, "Coupon has been used up by this customer"));
+        }
+    }
+
+    let claimed = sqlx::query!(
+        r#"
+            UPDATE coupons
+            SET times_used = times_used + 1
+            WHERE coupon_id = $1 AND (max_uses IS NULL OR times_used < max_uses)
+        "#,
+        coupon.coupon_id,
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(db_error)?;
+
+    if claimed.rows_affected() == 0 {
+        return Err(rejected(StatusCode::CONFLICT, "Coupon has been used up"));
+    }
+
+    Ok(coupon)
+}
+
+/// What `coupon` takes off a basket worth `basket`. Fixed amounts larger than
+/// the basket are capped to it.
+pub fn coupon_discount(coupon: &CouponModel, basket: Decimal) -> Result<Decimal, (StatusCode, Json<Value>)> {
+    if let Some(min_basket) = coupon.min_basket {
+        if basket < min_basket {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                    "success": false,
+                    "message": format!("Coupon needs a basket of at least {}", min_basket),
+                })),
+            ));
+        }
+    }
+
+    Ok(match coupon.discount_type {
+        DiscountType::Percent => round_money(basket * coupon.discount_value / Decimal::ONE_HUNDRED),
+        DiscountType::Fixed => coupon.discount_value.min(basket),
+    })
+}
+
+/// Records that `coupon_id` was redeemed on a sale. Must run in the same
+/// transaction as `claim_coupon`.
+pub async fn redeem_coupon(
+    conn: &mut PgConnection,
+    coupon_id: &str,
+    transaction_id: &str,
+    customer_ref: Option<&str>,
+    amount: Decimal,
+) -> Result<(), (StatusCode, Json<Value>)> {
+    sqlx::query!(
+        r#"
+            INSERT INTO coupon_redemptions (transaction_id, coupon_id, customer_ref, amount, redeemed_at)
+            VALUES ($1, $2, $3, $4, $5)
+        "#,
+        transaction_id,
+        coupon_id,
+        customer_ref.map(str::trim),
+        amount,
+        Utc::now(),
+    )
+    .execute(conn)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?;
+
+    Ok(())
+}
+
+/// Gives back the use of the coupon redeemed on a sale being voided, if any.
+pub async fn release_coupon(
+    conn: &mut PgConnection,
+    transaction_id: &str,
+) -> Result<(), (StatusCode, Json<Value>)> {
+    sqlx::query!(
+        r#"
+            UPDATE coupons
+            SET times_used = times_used - 1
+            FROM coupon_redemptions
+            WHERE coupon_redemptions.transaction_id = $1 AND coupons.coupon_id = coupon_redemptions.coupon_id
+        "#,
+        transaction_id,
+    )
+    .execute(conn)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn money(amount: &str) -> Decimal {
+        amount.parse().unwrap()
+    }
+
+    fn coupon(discount_type: DiscountType, discount_value: &str) -> CouponModel {
+        CouponModel {
+            coupon_id: "coupon".to_string(),
+            code: "SAVE".to_string(),
+            discount_type,
+            discount_value: money(discount_value),
+            valid_from: None,
+            valid_until: None,
+            max_uses: None,
+            max_uses_per_customer: None,
+            min_basket: None,
+            is_active: true,
+            times_used: 0,
+            created_at: None,
+            updated_at: None,
+        }
+    }
+
+    #[test]
+    fn rejects_baskets_below_the_minimum() {
+        let mut coupon = coupon(DiscountType::Fixed, "5");
+        coupon.min_basket = Some(money("20"));
+
+        let (status, body) = coupon_discount(&coupon, money("19.99")).unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["message"], "Coupon needs a basket of at least 20");
+        assert_eq!(coupon_discount(&coupon, money("20")).unwrap(), money("5"));
+    }
+
+    #[test]
+    fn rounds_percent_discounts_to_cents() {
+        let coupon = coupon(DiscountType::Percent, "15");
+
+        // 15% of 3.30 is 0.495, rounded half away from zero.
+        assert_eq!(coupon_discount(&coupon, money("3.30")).unwrap(), money("0.50"));
+        assert_eq!(coupon_discount(&coupon, money("19.99")).unwrap(), money("3.00"));
+    }
+
+    #[test]
+    fn caps_fixed_discounts_to_the_basket() {
+        let coupon = coupon(DiscountType::Fixed, "10");
+
+        assert_eq!(coupon_discount(&coupon, money("25")).unwrap(), money("10"));
+        assert_eq!(coupon_discount(&coupon, money("7.49")).unwrap(), money("7.49"));
+    }
+}
@@ -0,0 +1,255 @@
+use axum::{http::StatusCode, Json};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    models::{
+        invoices_model::InvoiceModel,
+        payments_model::TenderType,
+        transactions_model::TransactionModel},
+    services::{
+        pdf_service::{fit_text, Font, PdfDocument, PAGE_HEIGHT, PAGE_WIDTH},
+        tax_service::format_money,
+        transaction_service::embedded},
+};
+
+const MARGIN: f32 = 50.0;
+const RIGHT: f32 = PAGE_WIDTH - MARGIN;
+const TOP: f32 = PAGE_HEIGHT - MARGIN;
+/// Lines below this start a new page.
+const BOTTOM: f32 = 80.0;
+const FONT_SIZE: f32 = 9.0;
+const LEADING: f32 = 13.0;
+
+/// The right edges of the amount columns of the line table, after the
+/// description.
+const COLUMNS: [(&str, f32); 6] = [
+    ("Qty", 270.0),
+    ("Unit price", 330.0),
+    ("Discount", 390.0),
+    ("Net", 445.0),
+    ("Tax", 495.0),
+    ("Total", RIGHT),
+];
+const DESCRIPTION_WIDTH: f32 = 190.0;
+
+#[derive(Deserialize)]
+struct InvoiceItem {
+    product_name: Option<String>,
+    sku: Option<String>,
+    quantity: i32,
+    price: Decimal,
+    line_discount: Decimal,
+    line_tax: Decimal,
+    line_total: Decimal,
+}
+
+#[derive(Deserialize)]
+struct InvoiceTax {
+    rate_name: String,
+    rate: Decimal,
+    taxable_amount: Decimal,
+    tax_amount: Decimal,
+}
+
+#[derive(Deserialize)]
+struct InvoicePayment {
+    tender_type: TenderType,
+    amount: Decimal,
+}
+
+/// Invoice numbers are printed zero padded, e.g. `000042`.
+pub fn invoice_code(invoice_number: i64) -> String {
+    format!("{:06}", invoice_number)
+}
+
+/// Writes lines top to bottom, starting a new page when one is full.
+struct Cursor {
+    pdf: PdfDocument,
+    y: f32,
+}
+
+impl Cursor {
+    /// Moves down one line, returning whether a new page was started.
+    fn advance(&mut self, height: f32) -> bool {
+        self.y -= height;
+        if self.y < BOTTOM {
+            self.pdf.new_page();
+            self.y = TOP;
+            return true;
+        }
+        false
+    }
+
+    fn table_header(&mut self) {
+        self.pdf.text(MARGIN, self.y, Font::Bold, FONT_SIZE, "Description");
+        for (title, right) in COLUMNS {
+            self.pdf.text_right(right, self.y, Font::Bold, FONT_SIZE, title);
+        }
+        self.pdf.line(MARGIN, self.y - 4.0, RIGHT, self.y - 4.0, 0.5);
+        self.y -= 4.0;
+    }
+
+    /// A label and an amount right aligned in the totals block.
+    fn total(&mut self, label: &str, amount: Decimal, font: Font) {
+        self.advance(LEADING);
+        self.pdf.text_right(445.0, self.y, font, FONT_SIZE, label);
+        self.pdf.text_right(RIGHT, self.y, font, FONT_SIZE, &format_money(amount));
+    }
+}
+
+/// Renders `invoice` for the sale `transaction` as a PDF: our legal details as
+/// issued and the invoice number on top, then the customer's billing details, a
+/// line per item with its net amount and tax, a summary per tax rate and the
+/// totals.
+pub fn render_invoice(
+    invoice: &InvoiceModel,
+    transaction: &TransactionModel,
+) -> Result<Vec<u8>, (StatusCode, Json<Value>)> {
+    let items: Vec<InvoiceItem> = embedded(&transaction.transaction_items, "transaction_items")?;
+    let taxes: Vec<InvoiceTax> = embedded(&transaction.tax_breakdown, "tax_breakdown")?;
+    let payments: Vec<InvoicePayment> = embedded(&transaction.payments, "payments")?;
+
+    let mut cursor = Cursor { pdf: PdfDocument::new(), y: TOP };
+
+    cursor.pdf.text(MARGIN, cursor.y, Font::Bold, 16.0, &fit_text(&invoice.seller_name, Font::Bold, 16.0, 300.0));
+    cursor.pdf.text_right(RIGHT, cursor.y, Font::Bold, 20.0, "INVOICE");
+
+    let mut seller = invoice
+        .seller_address
+        .iter()
+        .flat_map(|address| address.lines())
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>();
+    if let Some(tax_id) = &invoice.seller_tax_id {
+        seller.push(format!("Tax ID: {}", tax_id));
+    }
+    let details = [
+        ("Invoice no.", invoice_code(invoice.invoice_number)),
+        ("Invoice date", invoice.issued_at.format("%Y-%m-%d").to_string()),
+        (
+            "Date of sale",
+            transaction.transaction_date.map(|date| date.format("%Y-%m-%d").to_string()).unwrap_or_default(),
+        ),
+        ("Receipt", transaction.receipt_number.clone()),
+    ];
+
+    let mut y = cursor.y - 8.0;
+    for line in &seller {
+        y -= LEADING;
+        cursor.pdf.text(MARGIN, y, Font::Regular, FONT_SIZE, &fit_text(line, Font::Regular, FONT_SIZE, 280.0));
+    }
+    let mut details_y = cursor.y - 8.0;
+    for (label, value) in &details {
+        details_y -= LEADING;
+        cursor.pdf.text(370.0, details_y, Font::Bold, FONT_SIZE, label);
+        cursor.pdf.text_right(RIGHT, details_y, Font::Regular, FONT_SIZE, value);
+    }
+    cursor.y = y.min(details_y) - 2.0 * LEADING;
+
+    cursor.pdf.text(MARGIN, cursor.y, Font::Bold, 10.0, "Bill to");
+    let mut customer = vec![(Font::Bold, invoice.customer_name.clone())];
+    if let Some(company_name) = &invoice.company_name {
+        customer.push((Font::Regular, company_name.clone()));
+    }
+    customer.extend(
+        invoice
+            .billing_address
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| (Font::Regular, line.to_string())),
+    );
+    if let Some(customer_tax_id) = &invoice.customer_tax_id {
+        customer.push((Font::Regular, format!("Tax ID: {}", customer_tax_id)));
+    }
+    if let Some(customer_email) = &invoice.customer_email {
+        customer.push((Font::Regular, customer_email.clone()));
+    }
+    for (font, line) in customer {
+        cursor.advance(LEADING);
+        cursor.pdf.text(MARGIN, cursor.y, font, FONT_SIZE, &fit_text(&line, font, FONT_SIZE, 300.0));
+    }
+
+    cursor.advance(2.0 * LEADING);
+    cursor.table_header();
+    for item in &items {
+        if cursor.advance(LEADING + 2.0) {
+            cursor.table_header();
+            cursor.advance(LEADING + 2.0);
+        }
+        let description = match (&item.product_name, &item.sku) {
+            (Some(product_name), Some(sku)) => format!("{} ({})", product_name, sku),
+            (Some(product_name), None) => product_name.clone(),
+            (None, _) => "Item".to_string(),
+        };
+        cursor.pdf.text(
+            MARGIN,
+            cursor.y,
+            Font::Regular,
+            FONT_SIZE,
+            &fit_text(&description, Font::Regular, FONT_SIZE, DESCRIPTION_WIDTH),
+        );
+        let amounts = [
+            item.quantity.to_string(),
+            format_money(item.price),
+            format_money(item.line_discount),
+            format_money(item.line_total - item.line_tax),
+            format_money(item.line_tax),
+            format_money(item.line_total),
+        ];
+        for ((_, right), amount) in COLUMNS.iter().zip(amounts) {
+            cursor.pdf.text_right(*right, cursor.y, Font::Regular, FONT_SIZE, &amount);
+        }
+    }
+    cursor.pdf.line(MARGIN, cursor.y - 5.0, RIGHT, cursor.y - 5.0, 0.5);
+    cursor.y -= 5.0;
+
+    let total = transaction.total_price.unwrap_or_default();
+    cursor.advance(4.0);
+    cursor.total("Net total", total - transaction.tax_total, Font::Regular);
+    cursor.total("Tax", transaction.tax_total, Font::Regular);
+    cursor.total("Total", total, Font::Bold);
+    for payment in &payments {
+        let tender = match payment.tender_type {
+            TenderType::Cash => "Paid in cash",
+            TenderType::Card => "Paid by card",
+            TenderType::Mobile => "Paid by mobile",
+            TenderType::Voucher => "Paid by voucher",
+            TenderType::Other => "Paid",
+        };
+        cursor.total(tender, payment.amount, Font::Regular);
+    }
+
+    if !taxes.is_empty() {
+        cursor.advance(2.0 * LEADING);
+        cursor.pdf.text(MARGIN, cursor.y, Font::Bold, 10.0, "Tax summary");
+        cursor.advance(LEADING + 2.0);
+        cursor.pdf.text(MARGIN, cursor.y, Font::Bold, FONT_SIZE, "Rate");
+        cursor.pdf.text_right(390.0, cursor.y, Font::Bold, FONT_SIZE, "Taxable amount");
+        cursor.pdf.text_right(RIGHT, cursor.y, Font::Bold, FONT_SIZE, "Tax");
+        for tax in &taxes {
+            cursor.advance(LEADING);
+            let rate = format!("{} {}%", tax.rate_name, tax.rate.normalize());
+            cursor.pdf.text(MARGIN, cursor.y, Font::Regular, FONT_SIZE, &fit_text(&rate, Font::Regular, FONT_SIZE, 250.0));
+            cursor.pdf.text_right(390.0, cursor.y, Font::Regular, FONT_SIZE, &format_money(tax.taxable_amount));
+            cursor.pdf.text_right(RIGHT, cursor.y, Font::Regular, FONT_SIZE, &format_money(tax.tax_amount));
+        }
+    }
+
+    if transaction.prices_include_tax || transaction.voided_at.is_some() {
+        cursor.advance(LEADING);
+    }
+    if transaction.prices_include_tax {
+        cursor.advance(LEADING);
+        cursor.pdf.text(MARGIN, cursor.y, Font::Regular, FONT_SIZE, "Unit prices include tax.");
+    }
+    if transaction.voided_at.is_some() {
+        cursor.advance(LEADING);
+        cursor.pdf.text(MARGIN, cursor.y, Font::Bold, FONT_SIZE, "This sale was voided.");
+    }
+
+    Ok(cursor.pdf.finish())
+}
@@ -0,0 +1,242 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// What a failed login is counted against. Usernames are throttled tightly;
+/// client IPs get more headroom because a whole shop may share one address.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ThrottleKey {
+    Username(String),
+    Ip(IpAddr),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ThrottlePolicy {
+    /// Failures allowed before any delay kicks in.
+    pub free_attempts: u32,
+    /// Failures after which the key is locked out.
+    pub lockout_after: u32,
+    pub lockout: Duration,
+    pub max_delay: Duration,
+    /// Failures older than this are forgotten.
+    pub window: Duration,
+}
+
+impl ThrottleKey {
+    fn policy(&self) -> ThrottlePolicy {
+        match self {
+            ThrottleKey::Username(_) => ThrottlePolicy {
+                free_attempts: 3,
+                lockout_after: 10,
+                lockout: Duration::from_secs(15 * 60),
+                max_delay: Duration::from_secs(60),
+                window: Duration::from_secs(15 * 60),
+            },
+            ThrottleKey::Ip(_) => ThrottlePolicy {
+                free_attempts: 10,
+                lockout_after: 50,
+                lockout: Duration::from_secs(15 * 60),
+                max_delay: Duration::from_secs(60),
+                window: Duration::from_secs(15 * 60),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Attempts {
+    failures: u32,
+    last_failure: Instant,
+}
+
+impl Attempts {
+    /// Earliest instant at which another attempt is allowed.
+    fn blocked_until(&self, policy: &ThrottlePolicy) -> Instant {
+        if self.failures >= policy.lockout_after {
+            return self.last_failure + policy.lockout;
+        }
+        if self.failures < policy.free_attempts {
+            return self.last_failure;
+        }
+        let exponent = (self.failures - policy.free_attempts).min(16);
+        let delay = Duration::from_secs(1u64 << exponent).min(policy.max_delay);
+        self.last_failure + delay
+    }
+
+    fn is_expired(&self, policy: &ThrottlePolicy, now: Instant) -> bool {
+        now >= self.blocked_until(policy) && now.duration_since(self.last_failure) >= policy.window
+    }
+}
+
+/// In-memory failed-login tracker with exponential back-off and lockout.
+/// Every method takes the current instant so the behaviour is deterministic.
+#[derive(Debug, Default)]
+pub struct LoginThrottle {
+    attempts: Mutex<HashMap<ThrottleKey, Attempts>>,
+}
+
+const PRUNE_THRESHOLD: usize = 10_000;
+
+impl LoginThrottle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts an attempt against every one of `keys` before it is made, or
+    /// returns how long the caller must wait if any of them is throttled. The
+    /// check and the count happen under one lock, so concurrent guesses are
+    /// counted one after the other instead of all passing the check at once.
+    /// Attempts that turn out not to fail are given back with `release`.
+    pub fn reserve(&self, keys: &[ThrottleKey], now: Instant) -> Result<(), Duration> {
+        let mut attempts = self.attempts.lock().unwrap();
+
+        if attempts.len() > PRUNE_THRESHOLD {
+            attempts.retain(|key, entry| !entry.is_expired(&key.policy(), now));
+        }
+
+        let wait = keys
+            .iter()
+            .filter_map(|key| {
+                let blocked_until = attempts.get(key)?.blocked_until(&key.policy());
+                blocked_until.checked_duration_since(now).filter(|wait| !wait.is_zero())
+            })
+            .max();
+        if let Some(wait) = wait {
+            return Err(wait);
+        }
+
+        for key in keys {
+            let policy = key.policy();
+            let entry = attempts.entry(key.clone()).or_insert(Attempts {
+                failures: 0,
+                last_failure: now,
+            });
+            if entry.is_expired(&policy, now) {
+                entry.failures = 0;
+            }
+            entry.failures += 1;
+            entry.last_failure = now;
+        }
+
+        Ok(())
+    }
+
+    /// Gives back an attempt taken by `reserve` that did not fail.
+    pub fn release(&self, keys: &[ThrottleKey]) {
+        let mut attempts = self.attempts.lock().unwrap();
+        for key in keys {
+            if let Some(entry) = attempts.get_mut(key) {
+                entry.failures = entry.failures.saturating_sub(1);
+                if entry.failures == 0 {
+                    attempts.remove(key);
+                }
+            }
+        }
+    }
+
+    /// Forgets the failures recorded against `key`, e.g. after a successful
+    /// login or when an admin lifts a lockout.
+    pub fn clear(&self, key: &ThrottleKey) {
+        self.attempts.lock().unwrap().remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn username() -> Vec<ThrottleKey> {
+        vec![ThrottleKey::Username("cashier".to_string())]
+    }
+
+    #[test]
+    fn delays_after_the_free_attempts() {
+        let throttle = LoginThrottle::new();
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            assert_eq!(throttle.reserve(&username(), start), Ok(()));
+        }
+        assert_eq!(throttle.reserve(&username(), start), Err(Duration::from_secs(1)));
+        assert_eq!(throttle.reserve(&username(), start + Duration::from_secs(1)), Ok(()));
+        assert_eq!(
+            throttle.reserve(&username(), start + Duration::from_secs(2)),
+            Err(Duration::from_secs(1)),
+        );
+    }
+
+    #[test]
+    fn concurrent_attempts_count_before_they_finish() {
+        let throttle = LoginThrottle::new();
+        let now = Instant::now();
+
+        let passed = (0..10).filter(|_| throttle.reserve(&username(), now).is_ok()).count();
+        assert_eq!(passed, 3);
+    }
+
+    #[test]
+    fn locks_out_after_too_many_failures() {
+        let throttle = LoginThrottle::new();
+        let mut now = Instant::now();
+
+        for _ in 0..10 {
+            while let Err(wait) = throttle.reserve(&username(), now) {
+                now += wait;
+            }
+        }
+        assert_eq!(throttle.reserve(&username(), now), Err(Duration::from_secs(15 * 60)));
+        assert!(throttle.reserve(&username(), now + Duration::from_secs(15 * 60 - 1)).is_err());
+        assert_eq!(throttle.reserve(&username(), now + Duration::from_secs(15 * 60)), Ok(()));
+    }
+
+    #[test]
+    fn forgets_failures_outside_the_window() {
+        let throttle = LoginThrottle::new();
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            throttle.reserve(&username(), start).unwrap();
+        }
+        let later = start + Duration::from_secs(15 * 60);
+        for _ in 0..3 {
+            assert_eq!(throttle.reserve(&username(), later), Ok(()));
+        }
+        assert!(throttle.reserve(&username(), later).is_err());
+    }
+
+    #[test]
+    fn release_and_clear_reset_the_count() {
+        let throttle = LoginThrottle::new();
+        let now = Instant::now();
+        let keys = username();
+
+        for _ in 0..3 {
+            throttle.reserve(&keys, now).unwrap();
+            throttle.release(&keys);
+        }
+        assert_eq!(throttle.reserve(&keys, now), Ok(()));
+
+        for _ in 0..2 {
+            throttle.reserve(&keys, now).unwrap();
+        }
+        assert!(throttle.reserve(&keys, now).is_err());
+        throttle.clear(&keys[0]);
+        assert_eq!(throttle.reserve(&keys, now), Ok(()));
+    }
+
+    #[test]
+    fn keys_are_throttled_independently() {
+        let throttle = LoginThrottle::new();
+        let now = Instant::now();
+        let ip = ThrottleKey::Ip(IpAddr::from([127, 0, 0, 1]));
+
+        for _ in 0..3 {
+            throttle.reserve(&[ip.clone(), ThrottleKey::Username("cashier".to_string())], now).unwrap();
+        }
+        assert!(throttle.reserve(&username(), now).is_err());
+        assert_eq!(throttle.reserve(&[ip], now), Ok(()));
+    }
+}
@@ -1,2 +1,16 @@
+pub mod audit_service;
+pub mod coupon_service;
 pub mod image_service;
-pub mod shutdown_service;
\ No newline at end of file
+pub mod invoice_service;
+pub mod jwt_keys;
+pub mod login_throttle;
+pub mod password_service;
+pub mod pdf_service;
+pub mod promotion_service;
+pub mod receipt_service;
+pub mod shutdown_service;
+pub mod store_service;
+pub mod tax_service;
+pub mod token_service;
+pub mod totp_service;
+pub mod transaction_service;
@@ -0,0 +1,268 @@
+use std::fmt::Write;
+
+/// A4 in PDF points.
+pub const PAGE_WIDTH: f32 = 595.0;
+pub const PAGE_HEIGHT: f32 = 842.0;
+
+/// The standard Helvetica faces every PDF reader ships, so nothing has to be
+/// embedded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Font {
+    Regular,
+    Bold,
+}
+
+impl Font {
+    fn resource(self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+        }
+    }
+
+    /// Advance widths of ASCII 32 to 126 in thousandths of the font size,
+    /// from the Adobe font metrics.
+    fn widths(self) -> &'static [u16; 95] {
+        const REGULAR: [u16; 95] = [
+            278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556, 556,
+            556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667, 611, 778,
+            722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278,
+            278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556,
+            556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+        ];
+        const BOLD: [u16; 95] = [
+            278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556, 556,
+            556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611, 975, 722, 722, 722, 722, 667, 611, 778,
+            722, 278, 556, 722, 611, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 333,
+            278, 333, 584, 556, 333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556, 278, 889, 611, 611,
+            611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584,
+        ];
+        match self {
+            Font::Regular => &REGULAR,
+            Font::Bold => &BOLD,
+        }
+    }
+}
+
+/// The width of `text` in points. Characters outside ASCII are counted as
+/// wide as a digit, which is close enough for Latin text.
+pub fn text_width(text: &str, font: Font, size: f32) -> f32 {
+    let widths = font.widths();
+    let units: u32 = text
+        .chars()
+        .map(|c| match c {
+            ' '..='~' => u32::from(widths[c as usize - 32]),
+            _ => 556,
+        })
+        .sum();
+    units as f32 * size / 1000.0
+}
+
+/// Shortens `text` with an ellipsis until it fits in `max_width` points.
+pub fn fit_text(text: &str, font: Font, size: f32, max_width: f32) -> String {
+    if text_width(text, font, size) <= max_width {
+        return text.to_string();
+    }
+    let mut fitted: String = text.to_string();
+    while !fitted.is_empty() && text_width(&format!("{}...", fitted), font, size) > max_width {
+        fitted.pop();
+    }
+    format!("{}...", fitted.trim_end())
+}
+
+/// Encodes `text` as a PDF literal string in WinAnsi. Latin-1 characters map
+/// to themselves, along with the euro sign, dashes and typographic quotes;
+/// anything else becomes `?`.
+fn literal(text: &str) -> Vec<u8> {
+    let mut bytes = vec![b'('];
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => bytes.extend([b'\\', c as u8]),
+            ' '..='~' | '\u{a0}'..='\u{ff}' => bytes.push(c as u32 as u8),
+            '€' => bytes.push(0x80),
+            '‘' => bytes.push(0x91),
+            '’' => bytes.push(0x92),
+            '“' => bytes.push(0x93),
+            '”' => bytes.push(0x94),
+            '•' => bytes.push(0x95),
+            '–' => bytes.push(0x96),
+            '—' => bytes.push(0x97),
+            _ => bytes.push(b'?'),
+        }
+    }
+    bytes.push(b')');
+    bytes
+}
+
+/// A PDF made of text and lines on A4 pages. Coordinates are in points from
+/// the bottom left corner of the page.
+pub struct PdfDocument {
+    pages: Vec<Vec<u8>>,
+}
+
+impl Default for PdfDocument {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PdfDocument {
+    /// Starts a document with one empty page.
+    pub fn new() -> Self {
+        Self { pages: vec![Vec::new()] }
+    }
+
+    pub fn new_page(&mut self) {
+        self.pages.push(Vec::new());
+    }
+
+    fn content(&mut self) -> &mut Vec<u8> {
+        self.pages.last_mut().expect("a document always has a page")
+    }
+
+    pub fn text(&mut self, x: f32, y: f32, font: Font, size: f32, text: &str) {
+        let content = self.content();
+        content.extend(format!("BT /{} {:.1} Tf {:.2} {:.2} Td ", font.resource(), size, x, y).into_bytes());
+        content.extend(literal(text));
+        content.extend(b" Tj ET\n");
+    }
+
+    /// Like `text`, but ending at `right`.
+    pub fn text_right(&mut self, right: f32, y: f32, font: Font, size: f32, text: &str) {
+        self.text(right - text_width(text, font, size), y, font, size, text);
+    }
+
+    pub fn line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, thickness: f32) {
+        let content = self.content();
+        content.extend(format!("{:.2} w {:.2} {:.2} m {:.2} {:.2} l S\n", thickness, x1, y1, x2, y2).into_bytes());
+    }
+
+    /// Serializes the document as PDF 1.4.
+    pub fn finish(self) -> Vec<u8> {
+        // Objects 1 and 2 are the catalog and page tree, 3 and 4 the fonts,
+        // followed by a page object and a content stream per page.
+        let page_count = self.pages.len();
+        let mut objects: Vec<Vec<u8>> = Vec::with_capacity(4 + 2 * page_count);
+
+        objects.push(b"<< /Type /Catalog /Pages 2 0 R >>".to_vec());
+        let kids = (0..page_count).fold(String::new(), |mut kids, page| {
+            let _ = write!(kids, "{} 0 R ", 5 + 2 * page);
+            kids
+        });
+        objects.push(format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.trim_end(), page_count).into_bytes());
+        for base_font in ["Helvetica", "Helvetica-Bold"] {
+            objects.push(
+                format!(
+                    "<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding /WinAnsiEncoding >>",
+                    base_font
+                )
+                .into_bytes(),
+            );
+        }
+        for (page, content) in self.pages.into_iter().enumerate() {
+            objects.push(
+                format!(
+                    "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+                     /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+                    PAGE_WIDTH,
+                    PAGE_HEIGHT,
+                    6 + 2 * page
+                )
+                .into_bytes(),
+            );
+            let mut stream = format!("<< /Length {} >>\nstream\n", content.len()).into_bytes();
+            stream.extend(content);
+            stream.extend(b"\nendstream");
+            objects.push(stream);
+        }
+
+        let mut pdf = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (index, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend(format!("{} 0 obj\n", index + 1).into_bytes());
+            pdf.extend(object);
+            pdf.extend(b"\nendobj\n");
+        }
+
+        let xref = pdf.len();
+        let mut trailer = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+        for offset in offsets {
+            let _ = writeln!(trailer, "{:010} 00000 n ", offset);
+        }
+        let _ = write!(
+            trailer,
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        );
+        pdf.extend(trailer.into_bytes());
+        pdf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The byte offset after `keyword`'s first occurrence from `start`.
+    fn find(pdf: &[u8], keyword: &[u8], start: usize) -> usize {
+        start + pdf[start..].windows(keyword.len()).position(|window| window == keyword).unwrap()
+    }
+
+    #[test]
+    fn escapes_and_encodes_literal_strings() {
+        assert_eq!(literal("a (b) \\c"), b"(a \\(b\\) \\\\c)".to_vec());
+        assert_eq!(literal("5 € – “ok” é"), b"(5 \x80 \x96 \x93ok\x94 \xe9)".to_vec());
+        assert_eq!(literal("日本"), b"(??)".to_vec());
+    }
+
+    #[test]
+    fn xref_offsets_point_at_their_objects() {
+        let mut pdf = PdfDocument::new();
+        pdf.text(50.0, 800.0, Font::Bold, 12.0, "Invoice (copy) – 10 €");
+        pdf.new_page();
+        pdf.line(50.0, 700.0, 545.0, 700.0, 0.5);
+        let pdf = pdf.finish();
+
+        // Everything from the cross-reference table on is ASCII.
+        let startxref = find(&pdf, b"startxref\n", 0) + b"startxref\n".len();
+        let xref: usize = std::str::from_utf8(&pdf[startxref..]).unwrap().lines().next().unwrap().parse().unwrap();
+        let text = std::str::from_utf8(&pdf[xref..]).unwrap();
+        assert!(text.starts_with("xref\n0 9\n0000000000 65535 f \n"));
+
+        // Catalog, page tree, two fonts, and a page and content stream per page.
+        let entries = text.lines().skip(3).take(8).collect::<Vec<_>>();
+        for (index, entry) in entries.iter().enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            // Entries are exactly 20 bytes with the line feed.
+            assert_eq!(entry.len(), 19);
+            assert!(entry.ends_with(" 00000 n "), "{}", entry);
+            let header = format!("{} 0 obj\n", index + 1);
+            assert!(pdf[offset..].starts_with(header.as_bytes()), "object {}", index + 1);
+        }
+        assert!(text.contains("trailer\n<< /Size 9 /Root 1 0 R >>"));
+        assert!(text.ends_with("%%EOF\n"));
+    }
+
+    #[test]
+    fn stream_lengths_match_their_content() {
+        let mut pdf = PdfDocument::new();
+        pdf.text(50.0, 800.0, Font::Regular, 9.0, "Größe (x)");
+        let pdf = pdf.finish();
+
+        let length_at = find(&pdf, b"/Length ", 0) + b"/Length ".len();
+        let length_end = find(&pdf, b" >>", length_at);
+        let length: usize = std::str::from_utf8(&pdf[length_at..length_end]).unwrap().parse().unwrap();
+        let start = find(&pdf, b"stream\n", length_end) + b"stream\n".len();
+        assert_eq!(&pdf[start + length..start + length + b"\nendstream".len()], b"\nendstream");
+    }
+
+    #[test]
+    fn fits_text_with_an_ellipsis() {
+        assert_eq!(fit_text("Pen", Font::Regular, 10.0, 100.0), "Pen");
+        let fitted = fit_text("A very long product name indeed", Font::Regular, 10.0, 60.0);
+        assert!(fitted.ends_with("..."));
+        assert!(text_width(&fitted, Font::Regular, 10.0) <= 60.0);
+    }
+}
@@ -0,0 +1,420 @@
+use std::collections::HashMap;
+use axum::{http::StatusCode, Json};
+use chrono::Utc;
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+use sqlx::PgConnection;
+
+use crate::{
+    models::{
+        coupons_model::CouponModel,
+        promotions_model::{AppliedDiscount, DiscountInput, DiscountSource, PromotionModel, PromotionType},
+        stores_model::ActiveStore},
+    services::{coupon_service::coupon_discount, tax_service::round_money},
+};
+
+/// A line of a sale before discounts.
+pub struct SaleLine {
+    pub product_id: String,
+    pub category_id: Option<String>,
+    pub price: Decimal,
+    pub quantity: i32,
+    pub amount: Decimal,
+    pub discount: Option<DiscountInput>,
+}
+
+/// Splits `total` over `weights` in proportion, in cents. Shares are rounded
+/// cumulatively so they always add up to `total`.
+pub fn allocate(total: Decimal, weights: &[Decimal]) -> Vec<Decimal> {
+    let sum: Decimal = weights.iter().sum();
+    if sum.is_zero() {
+        return vec![Decimal::ZERO; weights.len()];
+    }
+
+    let mut cumulative = Decimal::ZERO;
+    let mut allocated = Decimal::ZERO;
+    weights
+        .iter()
+        .map(|weight| {
+            cumulative += weight;
+            let up_to = round_money(total * cumulative / sum);
+            let share = up_to - allocated;
+            allocated = up_to;
+            share
+        })
+        .collect()
+}
+
+/// Works out the discount of every line and the discounts to record on the
+/// sale.
+///
+/// Promotions running right now are evaluated first, per product over all
+/// lines of that product so that scanning items one by one still counts
+/// towards buy X get Y and quantity breaks. Promotions do not stack; each
+/// product gets the one that saves the most. Manual line discounts apply to
+/// what is left of the line. The coupon and then the order discount apply to
+/// what is left of the sale, spread over the lines in proportion to their
+/// amounts.
+pub async fn discount_lines(
+    conn: &mut PgConnection,
+    store: &ActiveStore,
+    lines: &[SaleLine],
+    coupon: Option<&CouponModel>,
+    order_discount: Option<DiscountInput>,
+) -> Result<(Vec<Decimal>, Vec<AppliedDiscount>), (StatusCode, Json<Value>)> {
+    let now = Utc::now();
+
+    let promotions = sqlx::query_as!(
+        PromotionModel,
+        r#"
+            SELECT
+                promotion_id, promotion_name, promotion_type AS "promotion_type: PromotionType", product_id,
+                category_id, buy_quantity, get_quantity, min_quantity, discount_percent, starts_at, ends_at,
+                daily_start, daily_end, is_active, created_at, updated_at
+            FROM promotions
+            WHERE store_id = $1
+                AND is_active
+                AND (starts_at IS NULL OR starts_at <= $2)
+                AND (ends_at IS NULL OR ends_at > $2)
+                AND (daily_start IS NULL OR CASE
+                    WHEN daily_start <= daily_end THEN $3 >= daily_start AND $3 < daily_end
+                    ELSE $3 >= daily_start OR $3 < daily_end
+                END)
+            ORDER BY created_at, promotion_id
+        "#,
+        store.0,
+        now,
+        now.time(),
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?;
+
+    let mut discounts = vec![Decimal::ZERO; lines.len()];
+    let mut applied = Vec::new();
+
+    let mut products: Vec<(&str, Vec<usize>)> = Vec::new();
+    let mut product_index: HashMap<&str, usize> = HashMap::new();
+    for (index, line) in lines.iter().enumerate() {
+        let position = *product_index.entry(&line.product_id).or_insert_with(|| {
+            products.push((&line.product_id, Vec::new()));
+            products.len() - 1
+        });
+        products[position].1.push(index);
+    }
+
+    for (product_id, indexes) in &products {
+        let first = &lines[indexes[0]];
+        let too_large = || {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                    "success": false,
+                    "message": format!("Quantity of product {} is too large", product_id),
+                })),
+            )
+        };
+        let quantity = indexes
+            .iter()
+            .try_fold(0i32, |quantity, index| quantity.checked_add(lines[*index].quantity))
+            .ok_or_else(too_large)?;
+        let amount = indexes
+            .iter()
+            .try_fold(Decimal::ZERO, |amount, index| amount.checked_add(lines[*index].amount))
+            .ok_or_else(too_large)?;
+
+        let best = promotions
+            .iter()
+            .map(|promotion| (promotion, promotion_discount(promotion, product_id, first, quantity, amount)))
+            .filter(|(_, discount)| *discount > Decimal::ZERO)
+            .fold(None, |best: Option<(&PromotionModel, Decimal)>, candidate| match best {
+                Some((_, saved)) if saved >= candidate.1 => best,
+                _ => Some(candidate),
+            });
+
+        let Some((promotion, discount)) = best else {
+            continue;
+        };
+
+        let weights: Vec<Decimal> = indexes.iter().map(|index| lines[*index].amount).collect();
+        for (index, share) in indexes.iter().zip(allocate(discount.min(amount), &weights)) {
+            if share.is_zero() {
+                continue;
+            }
+            discounts[*index] += share;
+            applied.push(AppliedDiscount {
+                line_number: Some(*index as i32 + 1),
+                discount_source: DiscountSource::Promotion,
+                promotion_id: Some(promotion.promotion_id.clone()),
+                coupon_id: None,
+                description: promotion.promotion_name.clone(),
+                amount: share,
+            });
+        }
+    }
+
+    for (index, line) in lines.iter().enumerate() {
+        let Some(discount) = line.discount else {
+            continue;
+        };
+        let line_number = index as i32 + 1;
+        let (amount, description) =
+            manual_discount(discount, line.amount - discounts[index], &format!("line {}", line_number))?;
+        if amount.is_zero() {
+            continue;
+        }
+        discounts[index] += amount;
+        applied.push(AppliedDiscount {
+            line_number: Some(line_number),
+            discount_source: DiscountSource::Line,
+            promotion_id: None,
+            coupon_id: None,
+            description,
+            amount,
+        });
+    }
+
+    let remaining = |discounts: &[Decimal]| -> Vec<Decimal> {
+        lines
+            .iter()
+            .zip(discounts)
+            .map(|(line, discount)| line.amount - discount)
+            .collect()
+    };
+
+    if let Some(coupon) = coupon {
+        let basket = remaining(&discounts);
+        let amount = coupon_discount(coupon, basket.iter().sum())?;
+        for (discount, share) in discounts.iter_mut().zip(allocate(amount, &basket)) {
+            *discount += share;
+        }
+        applied.push(AppliedDiscount {
+            line_number: None,
+            discount_source: DiscountSource::Coupon,
+            promotion_id: None,
+            coupon_id: Some(coupon.coupon_id.clone()),
+            description: coupon.code.clone(),
+            amount,
+        });
+    }
+
+    if let Some(discount) = order_discount {
+        let basket = remaining(&discounts);
+        let (amount, description) = manual_discount(discount, basket.iter().sum(), "the order")?;
+        if !amount.is_zero() {
+            for (discount, share) in discounts.iter_mut().zip(allocate(amount, &basket)) {
+                *discount += share;
+            }
+            applied.push(AppliedDiscount {
+                line_number: None,
+                discount_source: DiscountSource::Order,
+                promotion_id: None,
+                coupon_id: None,
+                description,
+                amount,
+            });
+        }
+    }
+
+    Ok((discounts, applied))
+}
+
+/// What `promotion` takes off `quantity` units of a product worth `amount`.
+fn promotion_discount(
+    promotion: &PromotionModel,
+    product_id: &str,
+    line: &SaleLine,
+    quantity: i32,
+    amount: Decimal,
+) -> Decimal {
+    let percent_of = |amount: Decimal| round_money(amount * promotion.discount_percent / Decimal::ONE_HUNDRED);
+    let for_product = promotion.product_id.as_deref() == Some(product_id);
+
+    match promotion.promotion_type {
+        PromotionType::BuyXGetY if for_product => {
+            let (Some(buy), Some(get)) = (promotion.buy_quantity, promotion.get_quantity) else {
+                return Decimal::ZERO;
+            };
+            let discounted_units = quantity / (buy + get) * get;
+            percent_of(line.price * Decimal::from(discounted_units))
+        }
+        PromotionType::QuantityBreak if for_product => match promotion.min_quantity {
+            Some(min_quantity) if quantity >= min_quantity => percent_of(amount),
+            _ => Decimal::ZERO,
+        },
+        PromotionType::CategoryPercent
+            if promotion.category_id.is_some() && promotion.category_id == line.category_id =>
+        {
+            percent_of(amount)
+        }
+        _ => Decimal::ZERO,
+    }
+}
+
+/// Validates a manual discount against the `amount` it applies to and
+/// returns how much it takes off and how it reads on the sale. Fixed amounts
+/// larger than `amount` are capped to it.
+fn manual_discount(
+    discount: DiscountInput,
+    amount: Decimal,
+    applies_to: &str,
+) -> Result<(Decimal, String), (StatusCode, Json<Value>)> {
+    let bad_request = |message: String| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "success": false,
+                "message": message,
+            })),
+        )
+    };
+
+    match discount {
+        DiscountInput::Percent(percent) => {
+            if percent <= Decimal::ZERO || percent > Decimal::ONE_HUNDRED {
+                return Err(bad_request(format!("Discount on {} must be between 0 and 100 percent", applies_to)));
+            }
+            Ok((
+                round_money(amount * percent / Decimal::ONE_HUNDRED),
+                format!("{}% off", percent.normalize()),
+            ))
+        }
+        DiscountInput::Fixed(value) => {
+            let value = round_money(value);
+            if value <= Decimal::ZERO {
+                return Err(bad_request(format!("Discount on {} must be positive", applies_to)));
+            }
+            // Like fixed coupons, more than is left only brings it down to zero.
+            let value = value.min(amount);
+            Ok((value, format!("{:.2} off", value)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn money(amount: &str) -> Decimal {
+        amount.parse().unwrap()
+    }
+
+    fn line(price: &str, quantity: i32) -> SaleLine {
+        SaleLine {
+            product_id: "pen".to_string(),
+            category_id: Some("stationery".to_string()),
+            price: money(price),
+            quantity,
+            amount: money(price) * Decimal::from(quantity),
+            discount: None,
+        }
+    }
+
+    fn promotion(promotion_type: PromotionType, discount_percent: &str) -> PromotionModel {
+        PromotionModel {
+            promotion_id: "promotion".to_string(),
+            promotion_name: "Promotion".to_string(),
+            promotion_type,
+            product_id: Some("pen".to_string()),
+            category_id: None,
+            buy_quantity: None,
+            get_quantity: None,
+            min_quantity: None,
+            discount_percent: money(discount_percent),
+            starts_at: None,
+            ends_at: None,
+            daily_start: None,
+            daily_end: None,
+            is_active: true,
+            created_at: None,
+            updated_at: None,
+        }
+    }
+
+    fn discount_of(promotion: &PromotionModel, line: &SaleLine) -> Decimal {
+        promotion_discount(promotion, &line.product_id, line, line.quantity, line.amount)
+    }
+
+    #[test]
+    fn allocates_shares_that_add_up_to_the_total() {
+        let shares = allocate(money("10.00"), &[Decimal::ONE; 3]);
+        assert_eq!(shares, [money("3.33"), money("3.34"), money("3.33")]);
+
+        let shares = allocate(money("0.05"), &[money("1"), Decimal::ZERO, money("2"), Decimal::ZERO]);
+        assert_eq!(shares, [money("0.02"), Decimal::ZERO, money("0.03"), Decimal::ZERO]);
+        assert_eq!(shares.iter().sum::<Decimal>(), money("0.05"));
+
+        let weights = [money("19.99"), money("0.01"), money("7.50"), money("3.33")];
+        assert_eq!(allocate(money("4.27"), &weights).iter().sum::<Decimal>(), money("4.27"));
+
+        assert_eq!(allocate(money("1.00"), &[Decimal::ZERO; 2]), [Decimal::ZERO; 2]);
+    }
+
+    #[test]
+    fn buy_x_get_y_counts_complete_sets_only() {
+        let mut buy_two_get_one = promotion(PromotionType::BuyXGetY, "100");
+        buy_two_get_one.buy_quantity = Some(2);
+        buy_two_get_one.get_quantity = Some(1);
+
+        assert_eq!(discount_of(&buy_two_get_one, &line("1.50", 2)), Decimal::ZERO);
+        assert_eq!(discount_of(&buy_two_get_one, &line("1.50", 3)), money("1.50"));
+        assert_eq!(discount_of(&buy_two_get_one, &line("1.50", 5)), money("1.50"));
+        assert_eq!(discount_of(&buy_two_get_one, &line("1.50", 6)), money("3.00"));
+
+        buy_two_get_one.discount_percent = money("50");
+        assert_eq!(discount_of(&buy_two_get_one, &line("1.25", 3)), money("0.63"));
+    }
+
+    #[test]
+    fn quantity_breaks_start_at_the_minimum_quantity() {
+        let mut quantity_break = promotion(PromotionType::QuantityBreak, "10");
+        quantity_break.min_quantity = Some(4);
+
+        assert_eq!(discount_of(&quantity_break, &line("2.50", 3)), Decimal::ZERO);
+        assert_eq!(discount_of(&quantity_break, &line("2.50", 4)), money("1.00"));
+    }
+
+    #[test]
+    fn promotions_only_apply_to_their_product_or_category() {
+        let mut quantity_break = promotion(PromotionType::QuantityBreak, "10");
+        quantity_break.min_quantity = Some(1);
+        quantity_break.product_id = Some("ink".to_string());
+        assert_eq!(discount_of(&quantity_break, &line("2.50", 4)), Decimal::ZERO);
+
+        let mut category = promotion(PromotionType::CategoryPercent, "20");
+        category.product_id = None;
+        category.category_id = Some("stationery".to_string());
+        assert_eq!(discount_of(&category, &line("2.50", 4)), money("2.00"));
+    }
+
+    #[test]
+    fn caps_fixed_manual_discounts_to_the_amount() {
+        let (amount, description) = manual_discount(DiscountInput::Fixed(money("25")), money("19.99"), "line 1").unwrap();
+        assert_eq!(amount, money("19.99"));
+        assert_eq!(description, "19.99 off");
+
+        let (amount, _) = manual_discount(DiscountInput::Fixed(money("5.005")), money("19.99"), "line 1").unwrap();
+        assert_eq!(amount, money("5.01"));
+
+        assert!(manual_discount(DiscountInput::Fixed(Decimal::ZERO), money("19.99"), "line 1").is_err());
+    }
+
+    #[test]
+    fn rejects_percentages_outside_zero_to_one_hundred() {
+        let (amount, description) =
+            manual_discount(DiscountInput::Percent(money("12.5")), money("9.99"), "the order").unwrap();
+        assert_eq!(amount, money("1.25"));
+        assert_eq!(description, "12.5% off");
+
+        assert!(manual_discount(DiscountInput::Percent(Decimal::ZERO), money("9.99"), "the order").is_err());
+        assert!(manual_discount(DiscountInput::Percent(money("100.01")), money("9.99"), "the order").is_err());
+    }
+}
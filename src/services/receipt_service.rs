@@ -0,0 +1,483 @@
+use axum::{http::StatusCode, Json};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    models::{
+        payments_model::TenderType,
+        receipts_model::ReceiptStoreModel,
+        transactions_model::{TransactionModel, TransactionType}},
+    services::{tax_service::format_money, transaction_service::embedded},
+};
+
+/// One line of a receipt, independent of the output format.
+enum ReceiptLine {
+    /// The store name, printed large.
+    Title(String),
+    Centered(String),
+    /// A label on the left and an amount on the right.
+    Row(String, String),
+    /// Like `Row`, printed bold.
+    Total(String, String),
+    Rule,
+    Blank,
+}
+
+#[derive(Deserialize)]
+struct ReceiptItem {
+    line_number: i32,
+    product_name: Option<String>,
+    quantity: i32,
+    price: Decimal,
+}
+
+#[derive(Deserialize)]
+struct ReceiptDiscount {
+    line_number: Option<i32>,
+    description: String,
+    amount: Decimal,
+}
+
+#[derive(Deserialize)]
+struct ReceiptTax {
+    rate_name: String,
+    rate: Decimal,
+    tax_amount: Decimal,
+}
+
+#[derive(Deserialize)]
+struct ReceiptPayment {
+    tender_type: TenderType,
+    amount: Decimal,
+    reference: Option<String>,
+}
+
+fn tender_name(tender_type: TenderType) -> &'static str {
+    match tender_type {
+        TenderType::Cash => "Cash",
+        TenderType::Card => "Card",
+        TenderType::Mobile => "Mobile",
+        TenderType::Voucher => "Voucher",
+        TenderType::Other => "Other",
+    }
+}
+
+/// Lays out the receipt of `transaction`. Discounts of a line are printed
+/// under it, order discounts and coupons under the subtotal. Taxes are added
+/// before the total, or listed as included after it when prices include tax.
+fn layout(
+    transaction: &TransactionModel,
+    store: &ReceiptStoreModel,
+) -> Result<Vec<ReceiptLine>, (StatusCode, Json<Value>)> {
+    let items: Vec<ReceiptItem> = embedded(&transaction.transaction_items, "transaction_items")?;
+    let discounts: Vec<ReceiptDiscount> = embedded(&transaction.discounts, "discounts")?;
+    let taxes: Vec<ReceiptTax> = embedded(&transaction.tax_breakdown, "tax_breakdown")?;
+    let payments: Vec<ReceiptPayment> = embedded(&transaction.payments, "payments")?;
+
+    let mut lines = vec![ReceiptLine::Title(store.store_name.clone())];
+    let settings_lines = |text: &Option<String>| -> Vec<ReceiptLine> {
+        text.iter()
+            .flat_map(|text| text.lines())
+            .map(|line| ReceiptLine::Centered(line.trim().to_string()))
+            .collect()
+    };
+    lines.extend(settings_lines(&store.address));
+    if let Some(tax_id) = &store.tax_id {
+        lines.push(ReceiptLine::Centered(format!("Tax ID: {}", tax_id)));
+    }
+    if store.receipt_header.is_some() {
+        lines.push(ReceiptLine::Blank);
+        lines.extend(settings_lines(&store.receipt_header));
+    }
+    lines.push(ReceiptLine::Rule);
+
+    let kind = match transaction.transaction_type {
+        TransactionType::Sale => "Sale",
+        TransactionType::Refund => "Refund",
+    };
+    let date = transaction
+        .transaction_date
+        .map(|date| date.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_default();
+    lines.push(ReceiptLine::Row(kind.to_string(), date));
+    lines.push(ReceiptLine::Row("Receipt".to_string(), transaction.receipt_number.clone()));
+    if let Some(cashier_name) = &transaction.cashier_name {
+        lines.push(ReceiptLine::Row("Cashier".to_string(), cashier_name.clone()));
+    }
+    if let Some(register_name) = &transaction.register_name {
+        lines.push(ReceiptLine::Row("Register".to_string(), register_name.clone()));
+    }
+    if let Some(original_transaction_id) = &transaction.original_transaction_id {
+        lines.push(ReceiptLine::Row("Refund of".to_string(), original_transaction_id.clone()));
+    }
+    if transaction.voided_at.is_some() {
+        lines.push(ReceiptLine::Centered("*** VOIDED ***".to_string()));
+    }
+    lines.push(ReceiptLine::Rule);
+
+    let mut subtotal = Decimal::ZERO;
+    for item in &items {
+        let amount = item.price * Decimal::from(item.quantity);
+        subtotal += amount;
+        lines.push(ReceiptLine::Row(item.product_name.clone().unwrap_or_default(), format_money(amount)));
+        if item.quantity != 1 {
+            lines.push(ReceiptLine::Row(format!("  {} x {}", item.quantity, format_money(item.price)), String::new()));
+        }
+        for discount in discounts.iter().filter(|discount| discount.line_number == Some(item.line_number)) {
+            lines.push(ReceiptLine::Row(format!("  {}", discount.description), format_money(-discount.amount)));
+        }
+    }
+    lines.push(ReceiptLine::Rule);
+    lines.push(ReceiptLine::Row("Subtotal".to_string(), format_money(subtotal)));
+
+    for discount in discounts.iter().filter(|discount| discount.line_number.is_none()) {
+        lines.push(ReceiptLine::Row(discount.description.clone(), format_money(-discount.amount)));
+    }
+    // Refunds carry their share of the sale's discounts without listing them.
+    let listed: Decimal = discounts.iter().map(|discount| discount.amount).sum();
+    if transaction.discount_total != listed {
+        lines.push(ReceiptLine::Row("Discounts".to_string(), format_money(listed - transaction.discount_total)));
+    }
+
+    let tax_label = |tax: &ReceiptTax| format!("{} {}%", tax.rate_name, tax.rate.normalize());
+    let total = ReceiptLine::Total("TOTAL".to_string(), format_money(transaction.total_price.unwrap_or_default()));
+    if transaction.prices_include_tax {
+        lines.push(total);
+        for tax in &taxes {
+            lines.push(ReceiptLine::Row(format!("incl. {}", tax_label(tax)), format_money(tax.tax_amount)));
+        }
+    } else {
+        for tax in &taxes {
+            lines.push(ReceiptLine::Row(tax_label(tax), format_money(tax.tax_amount)));
+        }
+        lines.push(total);
+    }
+
+    if !payments.is_empty() {
+        lines.push(ReceiptLine::Blank);
+        for payment in &payments {
+            let label = match &payment.reference {
+                Some(reference) => format!("{} {}", tender_name(payment.tender_type), reference),
+                None => tender_name(payment.tender_type).to_string(),
+            };
+            lines.push(ReceiptLine::Row(label, format_money(payment.amount)));
+        }
+        if transaction.change_due > Decimal::ZERO {
+            lines.push(ReceiptLine::Row("Change".to_string(), format_money(transaction.change_due)));
+        }
+    }
+
+    if store.receipt_footer.is_some() {
+        lines.push(ReceiptLine::Rule);
+        lines.extend(settings_lines(&store.receipt_footer));
+    }
+
+    Ok(lines)
+}
+
+/// Breaks `text` into lines of at most `width` characters, at spaces where
+/// possible.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        while word.len() > width {
+            if !current.is_empty() {
+                lines.push(std::mem::take(&mut current));
+            }
+            lines.push(word.drain(..width).collect());
+        }
+        let word: String = word.into_iter().collect();
+        let length = current.chars().count();
+        if length > 0 && length + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(&word);
+    }
+    if !current.is_empty() || lines.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+fn centered(text: &str, width: usize) -> Vec<String> {
+    wrap(text, width)
+        .into_iter()
+        .map(|line| format!("{}{}", " ".repeat((width - line.chars().count()) / 2), line))
+        .collect()
+}
+
+/// A label and an amount on one line. Labels too long to fit are wrapped and
+/// the amount goes on their last line, or on a line of its own.
+fn row(label: &str, amount: &str, width: usize) -> Vec<String> {
+    let amount_width = amount.chars().count();
+    let indent = &label[..label.len() - label.trim_start().len()];
+    let mut lines: Vec<String> = wrap(label, width - indent.len())
+        .into_iter()
+        .map(|line| format!("{}{}", indent, line))
+        .collect();
+    let last = lines.pop().unwrap_or_default();
+    let last_width = last.chars().count();
+    if last_width + 1 + amount_width <= width || amount.is_empty() {
+        lines.push(format!("{}{}{}", last, " ".repeat(width.saturating_sub(last_width + amount_width)), amount));
+    } else {
+        lines.push(last);
+        lines.push(format!("{:>width$}", amount, width = width));
+    }
+    lines
+}
+
+fn text_lines(line: &ReceiptLine, width: usize) -> Vec<String> {
+    match line {
+        ReceiptLine::Title(text) | ReceiptLine::Centered(text) => centered(text, width),
+        ReceiptLine::Row(label, amount) | ReceiptLine::Total(label, amount) => row(label, amount, width),
+        ReceiptLine::Rule => vec!["-".repeat(width)],
+        ReceiptLine::Blank => vec![String::new()],
+    }
+}
+
+/// A plain-text receipt of `width` characters per line.
+pub fn render_text(
+    transaction: &TransactionModel,
+    store: &ReceiptStoreModel,
+    width: usize,
+) -> Result<String, (StatusCode, Json<Value>)> {
+    let mut receipt = String::new();
+    for line in layout(transaction, store)? {
+        for text in text_lines(&line, width) {
+            receipt.push_str(text.trim_end());
+            receipt.push('\n');
+        }
+    }
+    Ok(receipt)
+}
+
+const ESC: u8 = 0x1b;
+const GS: u8 = 0x1d;
+
+/// The receipt as ESC/POS commands for a thermal printer with `width`
+/// characters per line: the title in double height, totals in bold, and a
+/// partial cut at the end. Text outside ASCII is printed as `?`, which every
+/// code page supports.
+pub fn render_escpos(
+    transaction: &TransactionModel,
+    store: &ReceiptStoreModel,
+    width: usize,
+) -> Result<Vec<u8>, (StatusCode, Json<Value>)> {
+    let mut bytes = vec![ESC, b'@'];
+    for line in layout(transaction, store)? {
+        let (bold, double_height) = match line {
+            ReceiptLine::Title(_) => (true, true),
+            ReceiptLine::Total(_, _) => (true, false),
+            _ => (false, false),
+        };
+        if bold {
+            bytes.extend([ESC, b'E', 1]);
+        }
+        if double_height {
+            bytes.extend([GS, b'!', 0x01]);
+        }
+        for text in text_lines(&line, width) {
+            let printable = |c: char| if c.is_ascii() && !c.is_ascii_control() { c as u8 } else { b'?' };
+            bytes.extend(text.trim_end().chars().map(printable));
+            bytes.push(b'\n');
+        }
+        if double_height {
+            bytes.extend([GS, b'!', 0x00]);
+        }
+        if bold {
+            bytes.extend([ESC, b'E', 0]);
+        }
+    }
+    bytes.extend([ESC, b'd', 4, GS, b'V', 66, 0]);
+    Ok(bytes)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// A standalone HTML page that prints like a `width`-column receipt.
+pub fn render_html(
+    transaction: &TransactionModel,
+    store: &ReceiptStoreModel,
+    width: usize,
+) -> Result<String, (StatusCode, Json<Value>)> {
+    let mut body = String::new();
+    for line in layout(transaction, store)? {
+        let html = match line {
+            ReceiptLine::Title(text) => format!("<h1>{}</h1>", escape_html(&text)),
+            ReceiptLine::Centered(text) => format!("<p class=\"center\">{}</p>", escape_html(&text)),
+            ReceiptLine::Row(label, amount) => format!(
+                "<div class=\"row\"><span>{}</span><span>{}</span></div>",
+                escape_html(&label),
+                escape_html(&amount),
+            ),
+            ReceiptLine::Total(label, amount) => format!(
+                "<div class=\"row total\"><span>{}</span><span>{}</span></div>",
+                escape_html(&label),
+                escape_html(&amount),
+            ),
+            ReceiptLine::Rule => "<hr>".to_string(),
+            ReceiptLine::Blank => "<br>".to_string(),
+        };
+        body.push_str(&html);
+        body.push('\n');
+    }
+
+    Ok(format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body {{ font-family: monospace; max-width: {width}ch; margin: 1em auto; }}
+h1 {{ font-size: 1.4em; text-align: center; margin: 0; }}
+p {{ margin: 0; }}
+.center {{ text-align: center; }}
+.row {{ display: flex; justify-content: space-between; gap: 1ch; }}
+.row span {{ white-space: pre-wrap; }}
+.total {{ font-weight: bold; }}
+hr {{ border: 0; border-top: 1px dashed #000; }}
+</style>
+</head>
+<body>
+{body}</body>
+</html>
+"#,
+        title = escape_html(&store.store_name),
+        width = width,
+        body = body,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const LONG_NAME: &str =
+        "Extra large organic fair trade whole bean espresso roast coffee, family pack of twelve";
+
+    fn store() -> ReceiptStoreModel {
+        ReceiptStoreModel {
+            store_name: "Corner <Shop> & \"Deli\"".to_string(),
+            address: Some("1 Long Street\nSome Town".to_string()),
+            tax_id: Some("DE123456789".to_string()),
+            receipt_header: Some("Welcome!".to_string()),
+            receipt_footer: Some("Thank you for shopping with us, see you again soon".to_string()),
+        }
+    }
+
+    fn transaction() -> TransactionModel {
+        TransactionModel {
+            transaction_id: Some("sale".to_string()),
+            receipt_number: "S01-R02-000123".to_string(),
+            transaction_type: TransactionType::Sale,
+            original_transaction_id: None,
+            transaction_date: None,
+            cashier_id: None,
+            cashier_name: Some("Alex".to_string()),
+            register_id: None,
+            register_name: Some("Front".to_string()),
+            total_price: Some("123456789012.34".parse().unwrap()),
+            discount_total: "1.00".parse().unwrap(),
+            tax_total: "0".parse().unwrap(),
+            prices_include_tax: true,
+            change_due: "0".parse().unwrap(),
+            item_count: Some(3),
+            reason: None,
+            voided_at: None,
+            transaction_items: json!([
+                { "line_number": 1, "product_name": LONG_NAME, "quantity": 2, "price": "4.50" },
+                {
+                    "line_number": 2,
+                    "product_name": "Supercalifragilisticexpialidocious-limited-edition-gift-box",
+                    "quantity": 1,
+                    "price": "123456789003.34",
+                },
+                { "line_number": 3, "product_name": "Milk & <cookies>", "quantity": 1, "price": "1.00" },
+            ]),
+            discounts: json!([{ "line_number": 1, "description": "10% off", "amount": "1.00" }]),
+            tax_breakdown: json!([{ "rate_name": "VAT", "rate": "19", "tax_amount": "19711837495.99" }]),
+            payments: json!([
+                { "tender_type": "card", "amount": "123456789012.34", "reference": "VISA ending in 4242, auth 00981234" },
+            ]),
+        }
+    }
+
+    #[test]
+    fn wraps_long_words_and_labels_to_the_width() {
+        assert_eq!(wrap("abcdefghij", 4), ["abcd", "efgh", "ij"]);
+        assert_eq!(wrap("ab cd ef", 5), ["ab cd", "ef"]);
+        assert_eq!(wrap("", 5), [""]);
+
+        assert_eq!(row("Subtotal", "9.00", 20), ["Subtotal        9.00"]);
+        assert_eq!(row("A label that is long", "12345.67", 20), ["A label that is long", "            12345.67"]);
+        assert_eq!(row("  2 x 4.50", "", 20)[0].trim_end(), "  2 x 4.50");
+    }
+
+    #[test]
+    fn text_receipts_fit_their_width() {
+        for width in [42, 48] {
+            let receipt = render_text(&transaction(), &store(), width).unwrap();
+            for line in receipt.lines() {
+                assert!(line.chars().count() <= width, "{:?} is wider than {}", line, width);
+            }
+            assert!(receipt.contains("123456789012.34"));
+            assert!(receipt.contains("Supercalifragilisticexpialidocious"));
+        }
+    }
+
+    /// The printed text of an ESC/POS stream, without the commands
+    /// `render_escpos` emits.
+    fn without_commands(bytes: &[u8]) -> Vec<u8> {
+        let mut text = Vec::new();
+        let mut index = 0;
+        while index < bytes.len() {
+            index += match bytes[index..] {
+                [ESC, b'@', ..] => 2,
+                [ESC, b'E' | b'd', _, ..] | [GS, b'!', _, ..] => 3,
+                [GS, b'V', _, _, ..] => 4,
+                [byte, ..] => {
+                    text.push(byte);
+                    1
+                }
+                [] => unreachable!(),
+            };
+        }
+        text
+    }
+
+    #[test]
+    fn escpos_receipts_fit_their_width() {
+        for width in [42, 48] {
+            let receipt = render_escpos(&transaction(), &store(), width).unwrap();
+            for line in without_commands(&receipt).split(|&byte| byte == b'\n') {
+                assert!(line.len() <= width, "{:?} is wider than {}", String::from_utf8_lossy(line), width);
+            }
+        }
+    }
+
+    #[test]
+    fn escapes_html() {
+        assert_eq!(escape_html(r#"<a href="x">Tom & 'Jerry'</a>"#), "&lt;a href=&quot;x&quot;&gt;Tom &amp; &#39;Jerry&#39;&lt;/a&gt;");
+
+        let receipt = render_html(&transaction(), &store(), 42).unwrap();
+        assert!(receipt.contains("<title>Corner &lt;Shop&gt; &amp; &quot;Deli&quot;</title>"));
+        assert!(receipt.contains("<span>Milk &amp; &lt;cookies&gt;</span>"));
+        assert!(!receipt.contains("<Shop>"));
+        assert!(!receipt.contains("<cookies>"));
+    }
+}
@@ -0,0 +1,103 @@
+use axum::{http::StatusCode, Json};
+use serde_json::{json, Value};
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::models::auth_model::Role;
+
+/// Whether the account may act in `store_id`: the store must be active and
+/// the account either an owner or a member of it.
+pub async fn can_access(
+    executor: impl PgExecutor<'_>,
+    account_id: Uuid,
+    role: Role,
+    store_id: &str,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+            SELECT EXISTS (
+                SELECT 1
+                FROM stores
+                WHERE store_id = $1
+                    AND is_active
+                    AND ($2 = 'owner'::account_role OR EXISTS (
+                        SELECT 1 FROM account_stores WHERE account_id = $3 AND store_id = $1
+                    ))
+            ) AS "allowed!"
+        "#,
+        store_id,
+        role as Role,
+        account_id,
+    )
+    .fetch_one(executor)
+    .await
+}
+
+/// Picks the store a new session acts in. An explicit `requested` store must
+/// be accessible; without one, the account's only store is used.
+pub async fn resolve_session_store(
+    db: &PgPool,
+    account_id: Uuid,
+    role: Role,
+    requested: Option<&str>,
+) -> Result<String, (StatusCode, Json<Value>)> {
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": format!("Database error: {}", e),
+            })),
+        )
+    };
+
+    if let Some(store_id) = requested {
+        if !can_access(db, account_id, role, store_id).await.map_err(db_error)? {
+            return Err((
+                StatusCode::FORBIDDEN,
+                Json(json!({
+                    "success": false,
+                    "message": "You do not have access to this store",
+                })),
+            ));
+        }
+        return Ok(store_id.to_string());
+    }
+
+    let stores = sqlx::query_scalar!(
+        r#"
+            SELECT store_id
+            FROM stores
+            WHERE is_active
+                AND ($1 = 'owner'::account_role OR EXISTS (
+                    SELECT 1 FROM account_stores
+                    WHERE account_stores.account_id = $2 AND account_stores.store_id = stores.store_id
+                ))
+            ORDER BY store_id
+            LIMIT 2
+        "#,
+        role as Role,
+        account_id,
+    )
+    .fetch_all(db)
+    .await
+    .map_err(db_error)?;
+
+    match stores.as_slice() {
+        [store_id] => Ok(store_id.clone()),
+        [] => Err((
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "success": false,
+                "message": "Your account is not assigned to any store",
+            })),
+        )),
+        _ => Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "success": false,
+                "message": "You work in several stores, please provide a store_id",
+            })),
+        )),
+    }
+}
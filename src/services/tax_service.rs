@@ -0,0 +1,148 @@
+use rust_decimal::{Decimal, RoundingStrategy};
+
+use crate::models::{taxes_model::TaxRateModel, transactions_model::LineTax};
+
+/// Rounds to cents, halves away from zero.
+pub fn round_money(amount: Decimal) -> Decimal {
+    amount.round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero)
+}
+
+/// Amounts are always printed with two decimals.
+pub fn format_money(amount: Decimal) -> String {
+    format!("{:.2}", round_money(amount))
+}
+
+/// Taxes one line and returns the tax per rate and what the customer pays
+/// for the line.
+///
+/// `amount` is the line's price after discounts. Without included tax every
+/// rate is charged on it and rounded per line. With included tax the price
+/// already contains all rates; the contained tax is rounded once and split
+/// across the rates, the last one taking the rounding difference, so the
+/// line total stays exactly `amount`.
+pub fn tax_line(amount: Decimal, rates: &[TaxRateModel], prices_include_tax: bool) -> (Vec<LineTax>, Decimal) {
+    let line_tax = |rate: &TaxRateModel, taxable_amount: Decimal, tax_amount: Decimal| LineTax {
+        tax_rate_id: Some(rate.tax_rate_id.clone()),
+        rate_name: rate.rate_name.clone(),
+        rate: rate.rate,
+        taxable_amount,
+        tax_amount,
+    };
+
+    if !prices_include_tax {
+        let taxes: Vec<LineTax> = rates
+            .iter()
+            .map(|rate| line_tax(rate, amount, round_money(amount * rate.rate / Decimal::ONE_HUNDRED)))
+            .collect();
+        let line_total = amount + taxes.iter().map(|tax| tax.tax_amount).sum::<Decimal>();
+        return (taxes, line_total);
+    }
+
+    let combined_rate: Decimal = rates.iter().map(|rate| rate.rate).sum();
+    let net = amount * Decimal::ONE_HUNDRED / (Decimal::ONE_HUNDRED + combined_rate);
+    let contained_tax = round_money(amount - net);
+    let taxable_amount = amount - contained_tax;
+
+    let mut remaining = contained_tax;
+    let taxes = rates
+        .iter()
+        .enumerate()
+        .map(|(index, rate)| {
+            let tax_amount = if index + 1 == rates.len() {
+                remaining
+            } else {
+                round_money(net * rate.rate / Decimal::ONE_HUNDRED)
+            };
+            remaining -= tax_amount;
+            line_tax(rate, taxable_amount, tax_amount)
+        })
+        .collect();
+
+    (taxes, amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn money(amount: &str) -> Decimal {
+        amount.parse().unwrap()
+    }
+
+    fn rate(tax_rate_id: &str, rate: &str) -> TaxRateModel {
+        TaxRateModel {
+            tax_rate_id: tax_rate_id.to_string(),
+            rate_name: tax_rate_id.to_uppercase(),
+            rate: money(rate),
+        }
+    }
+
+    fn tax_amounts(taxes: &[LineTax]) -> Vec<Decimal> {
+        taxes.iter().map(|tax| tax.tax_amount).collect()
+    }
+
+    #[test]
+    fn rounds_half_cents_away_from_zero() {
+        assert_eq!(round_money(money("0.025")), money("0.03"));
+        assert_eq!(round_money(money("0.035")), money("0.04"));
+        assert_eq!(round_money(money("-0.025")), money("-0.03"));
+        assert_eq!(round_money(money("0.0249")), money("0.02"));
+        assert_eq!(format_money(money("7")), "7.00");
+    }
+
+    #[test]
+    fn adds_tax_on_top_of_exclusive_prices() {
+        let (taxes, line_total) = tax_line(money("10.00"), &[rate("vat", "19")], false);
+
+        assert_eq!(tax_amounts(&taxes), [money("1.90")]);
+        assert_eq!(taxes[0].taxable_amount, money("10.00"));
+        assert_eq!(line_total, money("11.90"));
+    }
+
+    #[test]
+    fn takes_tax_out_of_inclusive_prices() {
+        let (taxes, line_total) = tax_line(money("11.90"), &[rate("vat", "19")], true);
+
+        assert_eq!(tax_amounts(&taxes), [money("1.90")]);
+        assert_eq!(taxes[0].taxable_amount, money("10.00"));
+        assert_eq!(line_total, money("11.90"));
+    }
+
+    #[test]
+    fn rounds_exclusive_tax_per_line() {
+        // 5% of 0.50 is 0.025, which rounds up rather than to even.
+        let (taxes, line_total) = tax_line(money("0.50"), &[rate("vat", "5")], false);
+
+        assert_eq!(tax_amounts(&taxes), [money("0.03")]);
+        assert_eq!(line_total, money("0.53"));
+    }
+
+    #[test]
+    fn charges_every_rate_of_a_line() {
+        let rates = [rate("state", "5"), rate("city", "7")];
+
+        let (taxes, line_total) = tax_line(money("10.00"), &rates, false);
+        assert_eq!(tax_amounts(&taxes), [money("0.50"), money("0.70")]);
+        assert_eq!(line_total, money("11.20"));
+
+        // 10.00 contains 1.07 of tax at 12%; the last rate takes the rounding
+        // difference so the rates add up to it.
+        let (taxes, line_total) = tax_line(money("10.00"), &rates, true);
+        assert_eq!(tax_amounts(&taxes), [money("0.45"), money("0.62")]);
+        assert!(taxes.iter().all(|tax| tax.taxable_amount == money("8.93")));
+        assert_eq!(line_total, money("10.00"));
+    }
+
+    #[test]
+    fn leaves_exempt_lines_untaxed() {
+        for prices_include_tax in [false, true] {
+            let (taxes, line_total) = tax_line(money("4.99"), &[], prices_include_tax);
+            assert!(taxes.is_empty());
+            assert_eq!(line_total, money("4.99"));
+        }
+
+        let (taxes, line_total) = tax_line(money("4.99"), &[rate("zero", "0")], true);
+        assert_eq!(tax_amounts(&taxes), [Decimal::ZERO]);
+        assert_eq!(line_total, money("4.99"));
+    }
+}
@@ -0,0 +1,323 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use axum::{http::StatusCode, Json};
+use chrono::{Duration, Utc};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use sqlx::{PgConnection, PgExecutor};
+use uuid::Uuid;
+
+use crate::{models::auth_model::{MfaClaims, Role, TokenClaims}, AppState};
+
+pub const ACCESS_TOKEN_MINUTES: i64 = 15;
+pub const REFRESH_TOKEN_DAYS: i64 = 14;
+pub const REGISTER_TOKEN_MINUTES: i64 = 30;
+pub const MFA_TOKEN_MINUTES: i64 = 5;
+const MFA_AUDIENCE: &str = "mfa";
+
+#[derive(Debug, Serialize)]
+pub struct TokenPair {
+    pub token: String,
+    pub refresh_token: String,
+    pub expires_in: i64,
+}
+
+/// Random URL-safe secret for refresh tokens and other one-time codes.
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    data_encoding::BASE64URL_NOPAD.encode(&bytes)
+}
+
+/// Secrets handed out by the server are high-entropy, so a plain SHA-256 is
+/// enough to keep them unusable if the table leaks.
+pub fn hash_secret(secret: &str) -> String {
+    data_encoding::HEXLOWER.encode(&Sha256::digest(secret.as_bytes()))
+}
+
+pub fn encode_access_token(
+    app_state: &AppState,
+    account_id: Uuid,
+    role: Role,
+    session_id: Uuid,
+    store_id: &str,
+) -> Result<String, (StatusCode, Json<Value>)> {
+    let now = Utc::now();
+    let claims = TokenClaims {
+        sub: account_id.to_string(),
+        role,
+        sid: session_id.to_string(),
+        store_id: store_id.to_string(),
+        register_id: None,
+        iat: now.timestamp() as usize,
+        exp: (now + Duration::minutes(ACCESS_TOKEN_MINUTES)).timestamp() as usize,
+    };
+
+    encode_claims(app_state, &claims)
+}
+
+fn encode_claims(
+    app_state: &AppState,
+    claims: &TokenClaims,
+) -> Result<String, (StatusCode, Json<Value>)> {
+    app_state.jwt.encode(claims).map_err(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": "Failed to generate token",
+            })),
+        )
+    })
+}
+
+pub fn encode_mfa_token(
+    app_state: &AppState,
+    account_id: Uuid,
+    store_id: &str,
+) -> Result<String, (StatusCode, Json<Value>)> {
+    let now = Utc::now();
+    let claims = MfaClaims {
+        sub: account_id.to_string(),
+        aud: MFA_AUDIENCE.to_string(),
+        store_id: store_id.to_string(),
+        iat: now.timestamp() as usize,
+        exp: (now + Duration::minutes(MFA_TOKEN_MINUTES)).timestamp() as usize,
+    };
+
+    app_state.jwt.encode(&claims).map_err(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": "Failed to generate token",
+            })),
+        )
+    })
+}
+
+/// Returns the account a pending two-factor login belongs to and the store
+/// chosen at the password step.
+pub fn decode_mfa_token(
+    app_state: &AppState,
+    token: &str,
+) -> Result<(Uuid, String), (StatusCode, Json<Value>)> {
+    let invalid_token = || {
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(json!({
+                "success": false,
+                "message": "Invalid or expired login token, please log in again",
+            })),
+        )
+    };
+
+    let claims = app_state
+        .jwt
+        .decode::<MfaClaims>(token, Some(MFA_AUDIENCE))
+        .map_err(|_| invalid_token())?;
+
+    let account_id = Uuid::parse_str(&claims.sub).map_err(|_| invalid_token())?;
+    Ok((account_id, claims.store_id))
+}
+
+/// Stores a new refresh token for `session_id` and returns the raw value.
+pub async fn insert_refresh_token(
+    conn: &mut PgConnection,
+    session_id: Uuid,
+) -> Result<String, (StatusCode, Json<Value>)> {
+    let refresh_token = generate_secret();
+    let now = Utc::now();
+
+    sqlx::query!(
+        r#"
+            INSERT INTO refresh_tokens (token_hash, session_id, issued_at, expires_at)
+            VALUES ($1, $2, $3, $4)
+        "#,
+        hash_secret(&refresh_token),
+        session_id,
+        now,
+        now + Duration::days(REFRESH_TOKEN_DAYS),
+    )
+    .execute(conn)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?;
+
+    Ok(refresh_token)
+}
+
+/// Opens a new session for the account in `store_id` and returns its first
+/// token pair. The session `replaces`, if any, is ended in the same
+/// transaction, and must still be open.
+pub async fn issue_session(
+    app_state: &AppState,
+    account_id: Uuid,
+    role: Role,
+    store_id: &str,
+    replaces: Option<Uuid>,
+) -> Result<TokenPair, (StatusCode, Json<Value>)> {
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    };
+
+    let session_id = Uuid::new_v4();
+    let mut tx = app_state.db.begin().await.map_err(db_error)?;
+
+    if let Some(replaces) = replaces {
+        let revoked = sqlx::query!(
+            r#"
+                UPDATE sessions
+                SET revoked_at = $1
+                WHERE session_id = $2 AND account_id = $3 AND revoked_at IS NULL
+            "#,
+            Utc::now(),
+            replaces,
+            account_id,
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+        if revoked.rows_affected() == 0 {
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                Json(json!({
+                    "success": false,
+                    "message": "Your session has ended, please log in again",
+                })),
+            ));
+        }
+    }
+
+    sqlx::query!(
+        r#"
+            INSERT INTO sessions (session_id, account_id, store_id, created_at)
+            VALUES ($1, $2, $3, $4)
+        "#,
+        session_id,
+        account_id,
+        store_id,
+        Utc::now(),
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    let refresh_token = insert_refresh_token(&mut tx, session_id).await?;
+    tx.commit().await.map_err(db_error)?;
+
+    Ok(TokenPair {
+        token: encode_access_token(app_state, account_id, role, session_id, store_id)?,
+        refresh_token,
+        expires_in: ACCESS_TOKEN_MINUTES * 60,
+    })
+}
+
+/// Opens a short-lived, refresh-less cashier session bound to a register in
+/// `store_id`. Whoever was signed in on that register before is signed out.
+pub async fn issue_register_session(
+    app_state: &AppState,
+    account_id: Uuid,
+    register_id: &str,
+    store_id: &str,
+) -> Result<String, (StatusCode, Json<Value>)> {
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    };
+
+    let session_id = Uuid::new_v4();
+    let now = Utc::now();
+    let mut tx = app_state.db.begin().await.map_err(db_error)?;
+
+    sqlx::query!(
+        r#"
+            UPDATE sessions
+            SET revoked_at = $1
+            WHERE register_id = $2 AND revoked_at IS NULL
+        "#,
+        now,
+        register_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    sqlx::query!(
+        r#"
+            INSERT INTO sessions (session_id, account_id, register_id, store_id, created_at)
+            VALUES ($1, $2, $3, $4, $5)
+        "#,
+        session_id,
+        account_id,
+        register_id,
+        store_id,
+        now,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    let claims = TokenClaims {
+        sub: account_id.to_string(),
+        role: Role::Cashier,
+        sid: session_id.to_string(),
+        store_id: store_id.to_string(),
+        register_id: Some(register_id.to_string()),
+        iat: now.timestamp() as usize,
+        exp: (now + Duration::minutes(REGISTER_TOKEN_MINUTES)).timestamp() as usize,
+    };
+
+    encode_claims(app_state, &claims)
+}
+
+/// Ends every open session of an account. Access tokens stop working on their
+/// next request and refresh tokens can no longer be exchanged.
+pub async fn revoke_account_sessions(
+    executor: impl PgExecutor<'_>,
+    account_id: Uuid,
+) -> Result<u64, (StatusCode, Json<Value>)> {
+    let result = sqlx::query!(
+        r#"
+            UPDATE sessions
+            SET revoked_at = $1
+            WHERE account_id = $2 AND revoked_at IS NULL
+        "#,
+        Utc::now(),
+        account_id,
+    )
+    .execute(executor)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?;
+
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,311 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use axum::{http::StatusCode, Json};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha1::Sha1;
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::{models::auth_model::Role, services::token_service::hash_secret};
+
+pub const ISSUER: &str = "POS API";
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+const STEP_SECONDS: i64 = 30;
+const DIGITS: u32 = 6;
+/// Steps accepted either side of the current one to absorb clock drift.
+const ALLOWED_DRIFT: i64 = 1;
+
+/// New 160-bit shared secret, base32-encoded as authenticator apps expect.
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; 20];
+    OsRng.fill_bytes(&mut bytes);
+    data_encoding::BASE32_NOPAD.encode(&bytes)
+}
+
+pub fn otpauth_uri(username: &str, secret: &str) -> String {
+    let label = format!("{}:{}", ISSUER, username).replace(' ', "%20");
+    format!(
+        "otpauth://totp/{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        label,
+        secret,
+        ISSUER.replace(' ', "%20"),
+        DIGITS,
+        STEP_SECONDS,
+    )
+}
+
+/// RFC 6238 code for a given time step.
+fn code_at(secret: &[u8], step: i64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(&step.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([digest[offset], digest[offset + 1], digest[offset + 2], digest[offset + 3]])
+        & 0x7fff_ffff;
+    binary % 10u32.pow(DIGITS)
+}
+
+/// Checks `code` against `secret` at `unix_time` and returns the matching
+/// time step. Steps at or before `last_step` are rejected as replays.
+pub fn verify(secret: &str, code: &str, unix_time: i64, last_step: Option<i64>) -> Option<i64> {
+    let secret = data_encoding::BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+    let code = code.trim();
+    if code.len() != DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let code: u32 = code.parse().ok()?;
+    let current = unix_time.div_euclid(STEP_SECONDS);
+
+    (current - ALLOWED_DRIFT..=current + ALLOWED_DRIFT)
+        .filter(|step| last_step.is_none_or(|last| *step > last))
+        .find(|step| code_at(&secret, *step) == code)
+}
+
+/// Human-friendly one-time recovery codes such as `k3v9-q2ma`.
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut bytes = [0u8; 5];
+            OsRng.fill_bytes(&mut bytes);
+            let code = data_encoding::BASE32_NOPAD.encode(&bytes).to_ascii_lowercase();
+            format!("{}-{}", &code[..4], &code[4..])
+        })
+        .collect()
+}
+
+/// Recovery codes are compared case-insensitively and without the dash.
+pub fn normalize_recovery_code(code: &str) -> String {
+    code.trim().to_ascii_lowercase().replace('-', "")
+}
+
+fn db_error(e: sqlx::Error) -> (StatusCode, Json<Value>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({
+            "success": false,
+            "message": e.to_string(),
+        })),
+    )
+}
+
+fn invalid_code() -> (StatusCode, Json<Value>) {
+    (
+        StatusCode::UNAUTHORIZED,
+        Json(json!({
+            "success": false,
+            "message": "Invalid authentication code",
+        })),
+    )
+}
+
+/// Stores a fresh pending secret for the account and returns it together
+/// with its `otpauth://` URI. It only takes effect once confirmed.
+pub async fn begin_enrollment(
+    db: &PgPool,
+    account_id: Uuid,
+    username: &str,
+) -> Result<(String, String), (StatusCode, Json<Value>)> {
+    let secret = generate_secret();
+
+    sqlx::query!(
+        "UPDATE accounts SET totp_pending_secret = $1, updated_at = $2 WHERE id = $3",
+        secret,
+        Utc::now(),
+        account_id,
+    )
+    .execute(db)
+    .await
+    .map_err(db_error)?;
+
+    let uri = otpauth_uri(username, &secret);
+    Ok((secret, uri))
+}
+
+/// Activates the pending secret if `code` matches it and replaces any old
+/// recovery codes. Returns the new recovery codes in plain text.
+pub async fn confirm_enrollment(
+    db: &PgPool,
+    account_id: Uuid,
+    code: &str,
+) -> Result<Vec<String>, (StatusCode, Json<Value>)> {
+    let mut tx = db.begin().await.map_err(db_error)?;
+
+    let account = sqlx::query!(
+        "SELECT totp_pending_secret FROM accounts WHERE id = $1 FOR UPDATE",
+        account_id,
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    let pending_secret = account.totp_pending_secret.ok_or_else(|| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "success": false,
+                "message": "Two-factor enrollment has not been started",
+            })),
+        )
+    })?;
+
+    let step = verify(&pending_secret, code, Utc::now().timestamp(), None).ok_or_else(invalid_code)?;
+
+    sqlx::query!(
+        r#"
+            UPDATE accounts
+            SET totp_secret = totp_pending_secret, totp_pending_secret = NULL, totp_enabled = TRUE,
+                totp_last_step = $1, updated_at = $2
+            WHERE id = $3
+        "#,
+        step,
+        Utc::now(),
+        account_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    let recovery_codes = replace_recovery_codes(&mut tx, account_id).await?;
+    tx.commit().await.map_err(db_error)?;
+
+    Ok(recovery_codes)
+}
+
+async fn replace_recovery_codes(
+    conn: &mut PgConnection,
+    account_id: Uuid,
+) -> Result<Vec<String>, (StatusCode, Json<Value>)> {
+    sqlx::query!("DELETE FROM recovery_codes WHERE account_id = $1", account_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(db_error)?;
+
+    let recovery_codes = generate_recovery_codes();
+    let code_hashes: Vec<String> = recovery_codes
+        .iter()
+        .map(|code| hash_secret(&normalize_recovery_code(code)))
+        .collect();
+
+    sqlx::query!(
+        r#"
+            INSERT INTO recovery_codes (code_hash, account_id, created_at)
+            SELECT code_hash, $2, $3 FROM UNNEST($1::TEXT[]) AS code_hash
+        "#,
+        &code_hashes,
+        account_id,
+        Utc::now(),
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(db_error)?;
+
+    Ok(recovery_codes)
+}
+
+/// Checks a second factor for an enrolled account: either a current TOTP
+/// code or an unused recovery code, which is consumed.
+pub async fn verify_second_factor(
+    db: &PgPool,
+    account_id: Uuid,
+    code: &str,
+) -> Result<(), (StatusCode, Json<Value>)> {
+    let mut tx = db.begin().await.map_err(db_error)?;
+
+    let account = sqlx::query!(
+        "SELECT totp_secret, totp_last_step FROM accounts WHERE id = $1 AND totp_enabled FOR UPDATE",
+        account_id,
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(db_error)?
+    .ok_or_else(invalid_code)?;
+
+    let secret = account.totp_secret.unwrap_or_default();
+    if let Some(step) = verify(&secret, code, Utc::now().timestamp(), account.totp_last_step) {
+        sqlx::query!(
+            "UPDATE accounts SET totp_last_step = $1 WHERE id = $2",
+            step,
+            account_id,
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+    } else {
+        let used = sqlx::query!(
+            r#"
+                UPDATE recovery_codes
+                SET used_at = $1
+                WHERE account_id = $2 AND code_hash = $3 AND used_at IS NULL
+            "#,
+            Utc::now(),
+            account_id,
+            hash_secret(&normalize_recovery_code(code)),
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?
+        .rows_affected();
+
+        if used == 0 {
+            return Err(invalid_code());
+        }
+    }
+
+    tx.commit().await.map_err(db_error)?;
+    Ok(())
+}
+
+/// Whether the owner requires two-factor authentication for `role`.
+pub async fn is_required_for(
+    db: &PgPool,
+    role: Role,
+) -> Result<bool, (StatusCode, Json<Value>)> {
+    let required = sqlx::query_scalar!(
+        "SELECT require_totp FROM role_policies WHERE role = $1",
+        role as Role,
+    )
+    .fetch_optional(db)
+    .await
+    .map_err(db_error)?;
+
+    Ok(required.unwrap_or(false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The SHA-1 seed of RFC 6238 Appendix B.
+    const SEED: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn matches_the_rfc_6238_test_vectors() {
+        // Appendix B lists eight-digit codes; ours are their last six digits.
+        let vectors = [
+            (59, 94287082),
+            (1111111109, 7081804),
+            (1111111111, 14050471),
+            (1234567890, 89005924),
+            (2000000000, 69279037),
+            (20000000000, 65353130),
+        ];
+        for (unix_time, code) in vectors {
+            assert_eq!(code_at(SEED, unix_time / STEP_SECONDS), code % 1_000_000, "at {}", unix_time);
+        }
+    }
+
+    #[test]
+    fn verifies_codes_within_the_drift_and_rejects_replays() {
+        let secret = data_encoding::BASE32_NOPAD.encode(SEED);
+
+        assert_eq!(verify(&secret, "287082", 59, None), Some(1));
+        assert_eq!(verify(&secret, "287082", 89, None), Some(1));
+        assert_eq!(verify(&secret, "287082", 120, None), None);
+        assert_eq!(verify(&secret, "287082", 59, Some(1)), None);
+        assert_eq!(verify(&secret, "28708", 59, None), None);
+        assert_eq!(verify(&secret, "28708a", 59, None), None);
+    }
+}
@@ -0,0 +1,858 @@
+use std::collections::{BTreeMap, HashMap};
+use axum::{http::StatusCode, Json};
+use chrono::Utc;
+use rust_decimal::Decimal;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use sqlx::{PgConnection, PgExecutor};
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        coupons_model::CouponModel,
+        payments_model::{PaymentInput, TenderType},
+        promotions_model::{AppliedDiscount, DiscountSource},
+        stores_model::{ActiveStore, StockPolicy},
+        taxes_model::TaxRateModel,
+        transactions_model::{
+            StockLevelModel, TransactionInputModel, TransactionItem, TransactionModel, TransactionType}},
+    services::{
+        coupon_service::claim_coupon,
+        promotion_service::{discount_lines, SaleLine},
+        tax_service::tax_line},
+};
+
+/// Decodes `field`, one of the JSON lists `load_transactions` embeds in a
+/// transaction. A list that does not decode is reported rather than printed
+/// as empty.
+pub fn embedded<T: DeserializeOwned>(value: &Value, field: &str) -> Result<Vec<T>, (StatusCode, Json<Value>)> {
+    serde_json::from_value(value.clone()).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": format!("Could not read the {} of the transaction: {}", field, e),
+            })),
+        )
+    })
+}
+
+/// A sale priced from the catalog, ready to be recorded.
+pub struct PricedSale {
+    pub items: Vec<TransactionItem>,
+    pub discounts: Vec<AppliedDiscount>,
+    pub coupon: Option<CouponModel>,
+    pub total_price: Decimal,
+    pub discount_total: Decimal,
+    pub tax_total: Decimal,
+    pub prices_include_tax: bool,
+}
+
+/// Prices, discounts and taxes a sale from the store's catalog. Unknown,
+/// inactive and unpriced products are rejected, as are zero quantities. The
+/// product rows, and the coupon if one is given, stay locked until the
+/// surrounding transaction ends.
+pub async fn price_items(
+    conn: &mut PgConnection,
+    store: &ActiveStore,
+    input: &TransactionInputModel,
+) -> Result<PricedSale, (StatusCode, Json<Value>)> {
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    };
+    let bad_request = |message: String| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "success": false,
+                "message": message,
+            })),
+        )
+    };
+
+    let items = &input.transaction_items;
+    if items.is_empty() {
+        return Err(bad_request("transaction_items must not be empty".to_string()));
+    }
+
+    let product_ids: Vec<String> = items.iter().map(|item| item.product_id.clone()).collect();
+
+    let products = sqlx::query!(
+        r#"
+            SELECT
+                product_id, product_name, sku, price, products.is_active, products.category_id, category_name,
+                tax_class_id
+            FROM products
+            LEFT JOIN categories
+            ON products.category_id = categories.category_id
+            WHERE product_id = ANY($1) AND products.store_id = $2
+            ORDER BY product_id
+            FOR UPDATE OF products
+        "#,
+        &product_ids,
+        store.0,
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(db_error)?;
+
+    let products: HashMap<String, _> = products
+        .into_iter()
+        .map(|product| (product.product_id.clone(), product))
+        .collect();
+
+    let prices_include_tax = sqlx::query_scalar!(
+        "SELECT prices_include_tax FROM stores WHERE store_id = $1",
+        store.0,
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(db_error)?;
+
+    let tax_class_ids: Vec<String> = products.values().filter_map(|product| product.tax_class_id.clone()).collect();
+
+    let rates = sqlx::query!(
+        r#"
+            SELECT tax_class_id, tax_rate_id, rate_name, rate
+            FROM tax_rates
+            WHERE tax_class_id = ANY($1)
+            ORDER BY created_at, tax_rate_id
+        "#,
+        &tax_class_ids,
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(db_error)?;
+
+    let mut rates_by_class: HashMap<String, Vec<TaxRateModel>> = HashMap::new();
+    for rate in rates {
+        rates_by_class.entry(rate.tax_class_id).or_default().push(TaxRateModel {
+            tax_rate_id: rate.tax_rate_id,
+            rate_name: rate.rate_name,
+            rate: rate.rate,
+        });
+    }
+
+    let mut lines = Vec::with_capacity(items.len());
+    for item in items {
+        if item.quantity == 0 {
+            return Err(bad_request(format!("Quantity of product {} must be at least 1", item.product_id)));
+        }
+
+        let product = products
+            .get(&item.product_id)
+            .ok_or_else(|| bad_request(format!("Product {} not found", item.product_id)))?;
+
+        if !product.is_active {
+            return Err(bad_request(format!("Product {} is not available for sale", item.product_id)));
+        }
+
+        let price = product
+            .price
+            .ok_or_else(|| bad_request(format!("Product {} has no price", item.product_id)))?;
+
+        let quantity = i32::try_from(item.quantity)
+            .map_err(|_| bad_request(format!("Quantity of product {} is too large", item.product_id)))?;
+
+        let amount = price
+            .checked_mul(Decimal::from(quantity))
+            .ok_or_else(|| bad_request("Transaction total is too large".to_string()))?;
+
+        lines.push(SaleLine {
+            product_id: item.product_id.clone(),
+            category_id: product.category_id.clone(),
+            price,
+            quantity,
+            amount,
+            discount: item.discount,
+        });
+    }
+
+    let coupon = match &input.coupon_code {
+        Some(code) => Some(claim_coupon(conn, store, code, input.customer_ref.as_deref()).await?),
+        None => None,
+    };
+
+    let (line_discounts, discounts) = discount_lines(conn, store, &lines, coupon.as_ref(), input.discount).await?;
+
+    let mut sale = PricedSale {
+        items: Vec::with_capacity(lines.len()),
+        discounts,
+        coupon,
+        total_price: Decimal::ZERO,
+        discount_total: Decimal::ZERO,
+        tax_total: Decimal::ZERO,
+        prices_include_tax,
+    };
+
+    for (line, line_discount) in lines.into_iter().zip(line_discounts) {
+        let product = &products[&line.product_id];
+
+        let rates = product
+            .tax_class_id
+            .as_ref()
+            .and_then(|tax_class_id| rates_by_class.get(tax_class_id))
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let (taxes, line_total) = tax_line(line.amount - line_discount, rates, prices_include_tax);
+        let line_tax: Decimal = taxes.iter().map(|tax| tax.tax_amount).sum();
+
+        sale.total_price = sale
+            .total_price
+            .checked_add(line_total)
+            .ok_or_else(|| bad_request("Transaction total is too large".to_string()))?;
+        sale.discount_total += line_discount;
+        sale.tax_total += line_tax;
+
+        sale.items.push(TransactionItem {
+            product_id: Some(line.product_id),
+            product_name: product.product_name.clone(),
+            product_category: product.category_name.clone(),
+            sku: product.sku.clone(),
+            quantity: line.quantity,
+            price: line.price,
+            line_discount,
+            line_tax,
+            line_total,
+            original_line_number: None,
+            taxes,
+        });
+    }
+
+    Ok(sale)
+}
+
+/// Takes the sold quantities off stock and returns the new levels of the
+/// tracked products, plus a warning per product that went below zero when
+/// the store's policy is `warn`. Under `reject` such a sale fails instead.
+pub async fn decrement_stock(
+    conn: &mut PgConnection,
+    store: &ActiveStore,
+    items: &[TransactionItem],
+) -> Result<(Vec<StockLevelModel>, Vec<String>), (StatusCode, Json<Value>)> {
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    };
+
+    let stock_policy = sqlx::query_scalar!(
+        r#"SELECT stock_policy AS "stock_policy: StockPolicy" FROM stores WHERE store_id = $1"#,
+        store.0,
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(db_error)?;
+
+    let sold = items
+        .iter()
+        .filter_map(|item| Some((item.product_id.as_deref()?, item.quantity)));
+    let stock_levels = take_from_stock(conn, store, sold).await?;
+
+    let shortfalls: Vec<&StockLevelModel> = stock_levels.iter().filter(|level| level.stock < 0).collect();
+    if shortfalls.is_empty() {
+        return Ok((stock_levels, Vec::new()));
+    }
+
+    match stock_policy {
+        StockPolicy::Reject => Err((
+            StatusCode::CONFLICT,
+            Json(json!({
+                "success": false,
+                "message": format!("Insufficient stock for product {}", shortfalls[0].product_id),
+                "insufficient_stock": shortfalls
+                    .iter()
+                    .map(|level| &level.product_id)
+                    .collect::<Vec<_>>(),
+            })),
+        )),
+        StockPolicy::AllowNegative => Ok((stock_levels, Vec::new())),
+        StockPolicy::Warn => {
+            let warnings = shortfalls
+                .iter()
+                .map(|level| format!("Product {} is oversold, stock is now {}", level.product_id, level.stock))
+                .collect();
+            Ok((stock_levels, warnings))
+        }
+    }
+}
+
+/// Puts `(product_id, quantity)` pairs back on stock.
+pub async fn return_to_stock<'a>(
+    conn: &mut PgConnection,
+    store: &ActiveStore,
+    returned: impl Iterator<Item = (&'a str, i32)>,
+) -> Result<Vec<StockLevelModel>, (StatusCode, Json<Value>)> {
+    take_from_stock(conn, store, returned.map(|(product_id, quantity)| (product_id, quantity.saturating_neg()))).await
+}
+
+/// Subtracts the quantities of `(product_id, quantity)` pairs from the
+/// tracked products and returns their new stock levels.
+async fn take_from_stock<'a>(
+    conn: &mut PgConnection,
+    store: &ActiveStore,
+    taken: impl Iterator<Item = (&'a str, i32)>,
+) -> Result<Vec<StockLevelModel>, (StatusCode, Json<Value>)> {
+    let mut quantities: BTreeMap<&str, i32> = BTreeMap::new();
+    for (product_id, quantity) in taken {
+        let total = quantities.entry(product_id).or_default();
+        *total = total.saturating_add(quantity);
+    }
+    let (product_ids, quantities): (Vec<String>, Vec<i32>) = quantities
+        .into_iter()
+        .map(|(product_id, quantity)| (product_id.to_string(), quantity))
+        .unzip();
+
+    sqlx::query_as!(
+        StockLevelModel,
+        r#"
+            UPDATE products
+            SET stock = products.stock - taken.quantity, updated_at = $3
+            FROM UNNEST($1::TEXT[], $2::INT[]) AS taken (product_id, quantity)
+            WHERE products.product_id = taken.product_id
+                AND products.store_id = $4
+                AND products.stock IS NOT NULL
+            RETURNING products.product_id, products.stock AS "stock!"
+        "#,
+        &product_ids,
+        &quantities,
+        Utc::now(),
+        store.0,
+    )
+    .fetch_all(conn)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })
+}
+
+/// Stores the lines of a transaction, numbered from 1 in order, together
+/// with their taxes.
+pub async fn insert_transaction_items(
+    conn: &mut PgConnection,
+    transaction_id: &str,
+    items: &[TransactionItem],
+) -> Result<(), (StatusCode, Json<Value>)> {
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    };
+
+    let product_ids: Vec<Option<String>> = items.iter().map(|item| item.product_id.clone()).collect();
+    let product_names: Vec<Option<String>> = items.iter().map(|item| item.product_name.clone()).collect();
+    let product_categories: Vec<Option<String>> = items.iter().map(|item| item.product_category.clone()).collect();
+    let skus: Vec<Option<String>> = items.iter().map(|item| item.sku.clone()).collect();
+    let prices: Vec<Decimal> = items.iter().map(|item| item.price).collect();
+    let quantities: Vec<i32> = items.iter().map(|item| item.quantity).collect();
+    let line_discounts: Vec<Decimal> = items.iter().map(|item| item.line_discount).collect();
+    let line_taxes: Vec<Decimal> = items.iter().map(|item| item.line_tax).collect();
+    let line_totals: Vec<Decimal> = items.iter().map(|item| item.line_total).collect();
+    let original_line_numbers: Vec<Option<i32>> = items.iter().map(|item| item.original_line_number).collect();
+
+    sqlx::query!(
+        r#"
+            INSERT INTO transaction_items (
+                transaction_id, line_number, product_id, product_name, product_category, sku,
+                price, quantity, line_discount, line_tax, line_total, original_line_number
+            )
+            SELECT $1, line.line_number, line.product_id, line.product_name, line.product_category, line.sku,
+                line.price, line.quantity, line.line_discount, line.line_tax, line.line_total, line.original_line_number
+            FROM UNNEST(
+                $2::TEXT[], $3::TEXT[], $4::TEXT[], $5::TEXT[], $6::NUMERIC[], $7::INT[], $8::NUMERIC[], $9::NUMERIC[],
+                $10::NUMERIC[], $11::INT[]
+            ) WITH ORDINALITY AS line (
+                product_id, product_name, product_category, sku, price, quantity, line_discount, line_tax,
+                line_total, original_line_number, line_number
+            )
+        "#,
+        transaction_id,
+        &product_ids as &[Option<String>],
+        &product_names as &[Option<String>],
+        &product_categories as &[Option<String>],
+        &skus as &[Option<String>],
+        &prices,
+        &quantities,
+        &line_discounts,
+        &line_taxes,
+        &line_totals,
+        &original_line_numbers as &[Option<i32>],
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(db_error)?;
+
+    let mut line_numbers = Vec::new();
+    let mut tax_numbers = Vec::new();
+    let mut tax_rate_ids = Vec::new();
+    let mut rate_names = Vec::new();
+    let mut rates = Vec::new();
+    let mut taxable_amounts = Vec::new();
+    let mut tax_amounts = Vec::new();
+
+    for (line_number, item) in (1..).zip(items) {
+        for (tax_number, tax) in (1..).zip(&item.taxes) {
+            line_numbers.push(line_number);
+            tax_numbers.push(tax_number);
+            tax_rate_ids.push(tax.tax_rate_id.clone());
+            rate_names.push(tax.rate_name.clone());
+            rates.push(tax.rate);
+            taxable_amounts.push(tax.taxable_amount);
+            tax_amounts.push(tax.tax_amount);
+        }
+    }
+
+    sqlx::query!(
+        r#"
+            INSERT INTO transaction_item_taxes (
+                transaction_id, line_number, tax_number, tax_rate_id, rate_name, rate, taxable_amount, tax_amount
+            )
+            SELECT $1, tax.line_number, tax.tax_number, tax.tax_rate_id, tax.rate_name, tax.rate, tax.taxable_amount,
+                tax.tax_amount
+            FROM UNNEST($2::INT[], $3::INT[], $4::TEXT[], $5::TEXT[], $6::NUMERIC[], $7::NUMERIC[], $8::NUMERIC[])
+                AS tax (line_number, tax_number, tax_rate_id, rate_name, rate, taxable_amount, tax_amount)
+        "#,
+        transaction_id,
+        &line_numbers,
+        &tax_numbers,
+        &tax_rate_ids as &[Option<String>],
+        &rate_names,
+        &rates,
+        &taxable_amounts,
+        &tax_amounts,
+    )
+    .execute(conn)
+    .await
+    .map_err(db_error)?;
+
+    Ok(())
+}
+
+/// Checks that `payments` cover `total_price` and returns the change due.
+/// Only cash can be overpaid; card and other tenders must fit within the
+/// amount left to pay.
+pub fn change_due(total_price: Decimal, payments: &[PaymentInput]) -> Result<Decimal, (StatusCode, Json<Value>)> {
+    let bad_request = |message: String| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "success": false,
+                "message": message,
+            })),
+        )
+    };
+
+    let mut paid = Decimal::ZERO;
+    let mut paid_without_cash = Decimal::ZERO;
+
+    for payment in payments {
+        if payment.amount <= Decimal::ZERO {
+            return Err(bad_request("Payment amounts must be positive".to_string()));
+        }
+        paid = paid
+            .checked_add(payment.amount)
+            .ok_or_else(|| bad_request("Payment total is too large".to_string()))?;
+        if payment.tender_type != TenderType::Cash {
+            paid_without_cash += payment.amount;
+        }
+    }
+
+    if paid_without_cash > total_price {
+        return Err(bad_request(format!(
+            "Non-cash payments of {} exceed the total of {}",
+            paid_without_cash, total_price,
+        )));
+    }
+    if paid < total_price {
+        return Err(bad_request(format!(
+            "Payments of {} do not cover the total of {}",
+            paid, total_price,
+        )));
+    }
+
+    Ok(paid - total_price)
+}
+
+pub async fn insert_payments(
+    conn: &mut PgConnection,
+    transaction_id: &str,
+    payments: &[PaymentInput],
+) -> Result<(), (StatusCode, Json<Value>)> {
+    let tender_types: Vec<TenderType> = payments.iter().map(|payment| payment.tender_type).collect();
+    let amounts: Vec<Decimal> = payments.iter().map(|payment| payment.amount).collect();
+    let references: Vec<Option<String>> = payments.iter().map(|payment| payment.reference.clone()).collect();
+
+    sqlx::query!(
+        r#"
+            INSERT INTO payments (transaction_id, payment_number, tender_type, amount, reference)
+            SELECT $1, payment.payment_number, payment.tender_type, payment.amount, payment.reference
+            FROM UNNEST($2::tender_type[], $3::NUMERIC[], $4::TEXT[])
+                WITH ORDINALITY AS payment (tender_type, amount, reference, payment_number)
+        "#,
+        transaction_id,
+        &tender_types as &[TenderType],
+        &amounts,
+        &references as &[Option<String>],
+    )
+    .execute(conn)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?;
+
+    Ok(())
+}
+
+/// Records the promotions and manual discounts given on a sale, numbered
+/// from 1 in order. Must run after the lines are inserted.
+pub async fn insert_discounts(
+    conn: &mut PgConnection,
+    transaction_id: &str,
+    discounts: &[AppliedDiscount],
+) -> Result<(), (StatusCode, Json<Value>)> {
+    let line_numbers: Vec<Option<i32>> = discounts.iter().map(|discount| discount.line_number).collect();
+    let sources: Vec<DiscountSource> = discounts.iter().map(|discount| discount.discount_source).collect();
+    let promotion_ids: Vec<Option<String>> = discounts.iter().map(|discount| discount.promotion_id.clone()).collect();
+    let coupon_ids: Vec<Option<String>> = discounts.iter().map(|discount| discount.coupon_id.clone()).collect();
+    let descriptions: Vec<String> = discounts.iter().map(|discount| discount.description.clone()).collect();
+    let amounts: Vec<Decimal> = discounts.iter().map(|discount| discount.amount).collect();
+
+    sqlx::query!(
+        r#"
+            INSERT INTO transaction_discounts (
+                transaction_id, discount_number, line_number, discount_source, promotion_id, coupon_id, description,
+                amount
+            )
+            SELECT $1, discount.discount_number, discount.line_number, discount.discount_source,
+                discount.promotion_id, discount.coupon_id, discount.description, discount.amount
+            FROM UNNEST($2::INT[], $3::discount_source[], $4::TEXT[], $5::TEXT[], $6::TEXT[], $7::NUMERIC[])
+                WITH ORDINALITY AS discount (
+                    line_number, discount_source, promotion_id, coupon_id, description, amount, discount_number
+                )
+        "#,
+        transaction_id,
+        &line_numbers as &[Option<i32>],
+        &sources as &[DiscountSource],
+        &promotion_ids as &[Option<String>],
+        &coupon_ids as &[Option<String>],
+        &descriptions,
+        &amounts,
+    )
+    .execute(conn)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })?;
+
+    Ok(())
+}
+
+/// Takes the next receipt number of the register `register_id`, or of the
+/// store's sales without a register, e.g. `S01-R02-000123`. The sequence row
+/// stays locked until the surrounding transaction ends, so concurrent sales
+/// on a register are numbered one after the other, and a sale that is rolled
+/// back gives its number back.
+pub async fn next_receipt_number(
+    conn: &mut PgConnection,
+    store: &ActiveStore,
+    register_id: Option<&str>,
+) -> Result<String, (StatusCode, Json<Value>)> {
+    let db_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    };
+
+    let store_number = sqlx::query_scalar!("SELECT store_number FROM stores WHERE store_id = $1", store.0)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(db_error)?;
+
+    // Sales without a register are numbered as R00. A register that is gone
+    // or belongs to another store must not fall back to that sequence.
+    let register_number = match register_id {
+        Some(register_id) => sqlx::query_scalar!(
+            "SELECT register_number FROM registers WHERE register_id = $1 AND store_id = $2",
+            register_id,
+            store.0,
+        )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| {
+            (
+                StatusCode::FORBIDDEN,
+                Json(json!({
+                    "success": false,
+                    "message": "Your register does not belong to this store",
+                })),
+            )
+        })?,
+        None => 0,
+    };
+
+    let number = sqlx::query_scalar!(
+        r#"
+            INSERT INTO receipt_sequences (store_id, register_number, last_number)
+            VALUES ($1, $2, 1)
+            ON CONFLICT (store_id, register_number)
+            DO UPDATE SET last_number = receipt_sequences.last_number + 1
+            RETURNING last_number
+        "#,
+        store.0,
+        register_number,
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(db_error)?;
+
+    Ok(format!("S{:02}-R{:02}-{:06}", store_number, register_number, number))
+}
+
+/// The `LIKE` pattern matching receipt numbers that contain `search`,
+/// ignoring case. Receipt numbers are upper case, and the pattern can use
+/// their trigram index.
+pub fn receipt_number_pattern(search: &str) -> String {
+    let escaped = search.to_uppercase().replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+/// Loads the store's transactions, newest first, with their lines,
+/// discounts, taxes and payments embedded. With `transaction_id` only that one is loaded.
+/// `receipt_number` keeps the transactions whose receipt number contains it,
+/// `cashier_id` those recorded by that account.
+pub async fn load_transactions(
+    executor: impl PgExecutor<'_>,
+    store: &ActiveStore,
+    transaction_id: Option<&str>,
+    receipt_number: Option<&str>,
+    cashier_id: Option<Uuid>,
+    offset: i64,
+    limit: i64,
+) -> Result<Vec<TransactionModel>, (StatusCode, Json<Value>)> {
+    sqlx::query_as!(
+        TransactionModel,
+        r#"
+            SELECT
+                transaction_id, receipt_number, transaction_type AS "transaction_type: TransactionType",
+                original_transaction_id, transaction_date, cashier_id,
+                (SELECT full_name FROM accounts WHERE accounts.id = transactions.cashier_id) AS cashier_name,
+                register_id,
+                (
+                    SELECT register_name FROM registers WHERE registers.register_id = transactions.register_id
+                ) AS register_name,
+                total_price, discount_total, tax_total, prices_include_tax, change_due, item_count, reason, voided_at,
+                COALESCE((
+                    SELECT jsonb_agg(jsonb_build_object(
+                        'line_number', line_number,
+                        'product_id', product_id,
+                        'product_name', product_name,
+                        'product_category', product_category,
+                        'sku', sku,
+                        'quantity', quantity,
+                        'price', price::TEXT,
+                        'line_discount', line_discount::TEXT,
+                        'line_tax', line_tax::TEXT,
+                        'line_total', line_total::TEXT,
+                        'original_line_number', original_line_number,
+                        'taxes', COALESCE((
+                            SELECT jsonb_agg(jsonb_build_object(
+                                'tax_rate_id', tax_rate_id,
+                                'rate_name', rate_name,
+                                'rate', rate::TEXT,
+                                'taxable_amount', taxable_amount::TEXT,
+                                'tax_amount', tax_amount::TEXT
+                            ) ORDER BY tax_number)
+                            FROM transaction_item_taxes
+                            WHERE transaction_item_taxes.transaction_id = transaction_items.transaction_id
+                                AND transaction_item_taxes.line_number = transaction_items.line_number
+                        ), '[]')
+                    ) ORDER BY line_number)
+                    FROM transaction_items
+                    WHERE transaction_items.transaction_id = transactions.transaction_id
+                ), '[]') AS "transaction_items!",
+                COALESCE((
+                    SELECT jsonb_agg(jsonb_build_object(
+                        'line_number', line_number,
+                        'discount_source', discount_source,
+                        'promotion_id', promotion_id,
+                        'coupon_id', coupon_id,
+                        'description', description,
+                        'amount', amount::TEXT
+                    ) ORDER BY discount_number)
+                    FROM transaction_discounts
+                    WHERE transaction_discounts.transaction_id = transactions.transaction_id
+                ), '[]') AS "discounts!",
+                COALESCE((
+                    SELECT jsonb_agg(jsonb_build_object(
+                        'tax_rate_id', summary.tax_rate_id,
+                        'rate_name', summary.rate_name,
+                        'rate', summary.rate::TEXT,
+                        'taxable_amount', summary.taxable_amount::TEXT,
+                        'tax_amount', summary.tax_amount::TEXT
+                    ) ORDER BY summary.rate_name)
+                    FROM (
+                        SELECT tax_rate_id, rate_name, rate, SUM(taxable_amount) AS taxable_amount,
+                            SUM(tax_amount) AS tax_amount
+                        FROM transaction_item_taxes
+                        WHERE transaction_item_taxes.transaction_id = transactions.transaction_id
+                        GROUP BY tax_rate_id, rate_name, rate
+                    ) AS summary
+                ), '[]') AS "tax_breakdown!",
+                COALESCE((
+                    SELECT jsonb_agg(jsonb_build_object(
+                        'tender_type', tender_type,
+                        'amount', amount::TEXT,
+                        'reference', reference
+                    ) ORDER BY payment_number)
+                    FROM payments
+                    WHERE payments.transaction_id = transactions.transaction_id
+                ), '[]') AS "payments!"
+            FROM transactions
+            WHERE store_id = $1
+                AND ($2::TEXT IS NULL OR transaction_id = $2)
+                AND ($3::TEXT IS NULL OR receipt_number LIKE $3)
+                AND ($4::UUID IS NULL OR cashier_id = $4)
+            ORDER BY transaction_date DESC, transaction_id
+            OFFSET $5
+            LIMIT $6
+        "#,
+        store.0,
+        transaction_id,
+        receipt_number.map(receipt_number_pattern),
+        cashier_id,
+        offset,
+        limit,
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "success": false,
+                "message": e.to_string(),
+            })),
+        )
+    })
+}
+
+/// Loads one of the store's transactions with its lines embedded.
+pub async fn fetch_transaction(
+    executor: impl PgExecutor<'_>,
+    store: &ActiveStore,
+    transaction_id: &str,
+) -> Result<TransactionModel, (StatusCode, Json<Value>)> {
+    load_transactions(executor, store, Some(transaction_id), None, None, 0, 1)
+        .await?
+        .pop()
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "success": false,
+                    "message": "Transaction not found",
+                })),
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn money(amount: &str) -> Decimal {
+        amount.parse().unwrap()
+    }
+
+    fn paid(tender_type: TenderType, amount: &str) -> PaymentInput {
+        PaymentInput {
+            tender_type,
+            amount: money(amount),
+            reference: None,
+        }
+    }
+
+    fn message(error: (StatusCode, Json<Value>)) -> String {
+        assert_eq!(error.0, StatusCode::BAD_REQUEST);
+        error.1["message"].as_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn gives_change_on_cash_overpayment() {
+        assert_eq!(change_due(money("17.35"), &[paid(TenderType::Cash, "20")]).unwrap(), money("2.65"));
+        assert_eq!(change_due(money("17.35"), &[paid(TenderType::Cash, "17.35")]).unwrap(), Decimal::ZERO);
+    }
+
+    #[test]
+    fn rejects_non_cash_overpayment() {
+        let error = change_due(money("17.35"), &[paid(TenderType::Card, "20")]).unwrap_err();
+        assert_eq!(message(error), "Non-cash payments of 20 exceed the total of 17.35");
+
+        let split = [paid(TenderType::Voucher, "10"), paid(TenderType::Card, "10")];
+        assert!(change_due(money("17.35"), &split).is_err());
+    }
+
+    #[test]
+    fn splits_a_sale_over_cash_and_card() {
+        let split = [paid(TenderType::Card, "10"), paid(TenderType::Cash, "10")];
+        assert_eq!(change_due(money("17.35"), &split).unwrap(), money("2.65"));
+
+        let exact = [paid(TenderType::Card, "17.35"), paid(TenderType::Cash, "0.01")];
+        assert_eq!(change_due(money("17.35"), &exact).unwrap(), money("0.01"));
+    }
+
+    #[test]
+    fn rejects_non_positive_amounts() {
+        for amount in ["0", "-5"] {
+            let payments = [paid(TenderType::Cash, "20"), paid(TenderType::Cash, amount)];
+            assert_eq!(message(change_due(money("17.35"), &payments).unwrap_err()), "Payment amounts must be positive");
+        }
+    }
+
+    #[test]
+    fn rejects_underpayment() {
+        let split = [paid(TenderType::Card, "10"), paid(TenderType::Cash, "7.34")];
+        let error = change_due(money("17.35"), &split).unwrap_err();
+        assert_eq!(message(error), "Payments of 17.34 do not cover the total of 17.35");
+
+        assert!(change_due(money("17.35"), &[]).is_err());
+        assert_eq!(change_due(Decimal::ZERO, &[]).unwrap(), Decimal::ZERO);
+    }
+}